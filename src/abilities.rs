@@ -0,0 +1,347 @@
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+use crate::input::InputLayout;
+use crate::arena::{offset, Arena, Wrap};
+use crate::collision::Wall;
+use crate::hazards::MudZone;
+use crate::items::Item;
+use crate::modes::{KnockedOut, Tagged};
+use crate::projectile::Projectile;
+use crate::sim::{ready, Cooldown, FrameCounter};
+use crate::teams::{Team, TeamEnergy};
+use crate::{Config, Player, Velocity, PLAYER_RADIUS};
+
+pub const MAGNET_RANGE: f32 = 200.;
+// how far a pickup right next to the player moves per frame, falling off to nothing at the range
+pub const MAGNET_PULL: f32 = 4.;
+pub const MAGNET_ENERGY: u32 = 120;
+// frames before a magnet that ran dry can be used again
+pub const MAGNET_COOLDOWN: u32 = 90;
+
+pub const GRAPPLE_RANGE: f32 = 300.;
+// added to the player's velocity while hooked, in units per second
+pub const GRAPPLE_PULL: f32 = 400.;
+
+pub const SLOW_FIELD_HALF_SIZE: f32 = 80.;
+pub const SLOW_FIELD_FACTOR: f32 = 0.4;
+pub const SLOW_FIELD_LIFETIME: u32 = 180;
+pub const SLOW_FIELD_COOLDOWN: u32 = 360;
+// taken from the team's pool with `TeamEnergy`
+pub const GRAPPLE_ENERGY: u32 = 15;
+pub const SLOW_FIELD_ENERGY: u32 = 40;
+
+// anything a magnet can pull in
+type Pullable = (Or<(With<Item>, With<Projectile>)>, With<Rollback>, Without<Player>);
+
+// Held to pull in nearby pickups and enemy shots. Drains while held and
+// recharges while released, running dry puts it on cooldown.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Magnet {
+    pub energy: u32,
+    pub active: bool,
+}
+
+impl Default for Magnet {
+    fn default() -> Self {
+        Self { energy: MAGNET_ENERGY, active: false }
+    }
+}
+
+// offset moving something `delta` away from the magnet toward it, stronger the closer it is
+pub fn magnet_pull(delta: Vec2) -> Vec2 {
+    let dist = delta.length();
+    if dist >= MAGNET_RANGE || dist == 0. {
+        return Vec2::ZERO;
+    }
+    let step = (MAGNET_PULL * (1. - dist / MAGNET_RANGE)).min(dist);
+    delta / dist * step
+}
+
+type MagnetHolder = (
+    &'static Transform,
+    &'static Player,
+    &'static KnockedOut,
+    &'static mut Magnet,
+    &'static mut Cooldown<Magnet>,
+);
+
+pub fn use_magnets(
+    mut players: Query<MagnetHolder, With<Rollback>>,
+    mut pulled: Query<(&mut Transform, Option<&Projectile>), Pullable>,
+    inputs: Res<PlayerInputs<Config>>,
+    frame: Res<FrameCounter>,
+    wrap: Option<Res<Wrap>>,
+    arena: Res<Arena>
+) {
+    let wrap = wrap.map(|_| arena.half_size);
+    let mut magnets: Vec<_> = players.iter_mut().collect();
+    magnets.sort_by_key(|(_, player, ..)| player.id);
+
+    for (transform, player, knocked_out, magnet, cooldown) in magnets.iter_mut() {
+        let held = InputLayout::Magnet.is_set(inputs[player.id].0.wasd) && !knocked_out.out;
+        magnet.active = held && magnet.energy > 0 && ready(cooldown, frame.0);
+        if !magnet.active {
+            if !held {
+                magnet.energy = (magnet.energy + 1).min(MAGNET_ENERGY);
+            }
+            continue;
+        }
+        magnet.energy -= 1;
+        if magnet.energy == 0 {
+            cooldown.trigger(frame.0);
+        }
+
+        let center = transform.translation.truncate();
+        for (mut target, projectile) in pulled.iter_mut() {
+            if projectile.is_some_and(|p| p.owner == player.id) {
+                continue;
+            }
+            let pull = magnet_pull(offset(target.translation.truncate(), center, wrap));
+            target.translation += pull.extend(0.);
+        }
+    }
+}
+
+pub fn draw_magnet_auras(mut gizmos: Gizmos, query: Query<(&Transform, &Magnet)>) {
+    for (transform, magnet) in query.iter() {
+        if magnet.active {
+            let alpha = 0.2 + 0.4 * magnet.energy as f32 / MAGNET_ENERGY as f32;
+            gizmos.circle_2d(transform.translation.truncate(), MAGNET_RANGE, Color::srgba(0.4, 0.7, 1., alpha));
+        }
+    }
+}
+
+// Where the hook is stuck, if anywhere. Held to fire at the closest wall in range and
+// reel in, letting go or reaching the anchor lets go of it.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Grapple {
+    pub anchor: Option<Vec2>,
+}
+
+// nearest point on any wall within range, equally near walls go to the lowest x then y
+pub fn grapple_anchor(pos: Vec2, walls: &[(Vec2, Vec2)]) -> Option<Vec2> {
+    walls
+        .iter()
+        .map(|&(center, half_size)| pos.clamp(center - half_size, center + half_size))
+        .filter(|point| point.distance(pos) <= GRAPPLE_RANGE)
+        .min_by(|a, b| {
+            a.distance(pos)
+                .total_cmp(&b.distance(pos))
+                .then(a.x.total_cmp(&b.x))
+                .then(a.y.total_cmp(&b.y))
+        })
+}
+
+type Grappler = (
+    &'static Transform,
+    &'static Player,
+    &'static Team,
+    &'static mut Velocity,
+    &'static mut Grapple,
+    &'static Tagged,
+    &'static KnockedOut,
+);
+
+pub fn use_grapples(
+    mut players: Query<Grappler, With<Rollback>>,
+    walls: Query<(&Transform, &Wall), Without<Player>>,
+    inputs: Res<PlayerInputs<Config>>,
+    mut energy: Option<ResMut<TeamEnergy>>
+) {
+    let walls: Vec<_> = walls.iter().map(|(t, wall)| (t.translation.truncate(), wall.half_size)).collect();
+    let mut players: Vec<_> = players.iter_mut().collect();
+    // teammates draw from the same pool in handle order
+    players.sort_by_key(|(_, player, ..)| player.id);
+    for (transform, player, team, vel, grapple, tagged, knocked_out) in players.iter_mut() {
+        let pos = transform.translation.truncate();
+        // players held in place by a mode can't reel themselves away either
+        if !InputLayout::Hook.is_set(inputs[player.id].0.wasd) || tagged.frozen || knocked_out.out {
+            grapple.anchor = None;
+            continue;
+        }
+        if grapple.anchor.is_none() {
+            grapple.anchor = grapple_anchor(pos, &walls)
+                .filter(|_| energy.as_mut().is_none_or(|e| e.spend(**team, GRAPPLE_ENERGY)));
+        }
+        let Some(anchor) = grapple.anchor else {
+            continue;
+        };
+        let offset = anchor - pos;
+        if offset.length() <= PLAYER_RADIUS {
+            grapple.anchor = None;
+            continue;
+        }
+        let pull = offset.normalize() * GRAPPLE_PULL;
+        vel.x += pull.x;
+        vel.y += pull.y;
+    }
+}
+
+pub fn draw_grapples(mut gizmos: Gizmos, query: Query<(&Transform, &Grapple)>) {
+    for (transform, grapple) in query.iter() {
+        if let Some(anchor) = grapple.anchor {
+            gizmos.line_2d(transform.translation.truncate(), anchor, Color::srgb(0.8, 0.7, 0.5));
+            gizmos.circle_2d(anchor, 4., Color::srgb(0.8, 0.7, 0.5));
+        }
+    }
+}
+
+// A mud zone dropped where its owner stood, slowing everyone else until it expires.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SlowField {
+    pub owner: usize,
+    pub zone: MudZone,
+    pub deployed_at: u32,
+}
+
+pub fn slow_field(owner: usize, center: Vec2, frame: u32) -> (SlowField, Transform) {
+    let zone = MudZone {
+        center,
+        half_size: Vec2::splat(SLOW_FIELD_HALF_SIZE),
+        factor: SLOW_FIELD_FACTOR,
+    };
+    (SlowField { owner, zone, deployed_at: frame }, Transform::from_translation(center.extend(-2.)))
+}
+
+pub fn deploy_slow_fields(
+    mut commands: Commands,
+    mut players: Query<(&Transform, &Player, &Team, &mut Cooldown<SlowField>), With<Rollback>>,
+    fields: Query<(Entity, &SlowField), With<Rollback>>,
+    inputs: Res<PlayerInputs<Config>>,
+    frame: Res<FrameCounter>,
+    mut energy: Option<ResMut<TeamEnergy>>
+) {
+    for (entity, field) in fields.iter() {
+        if frame.elapsed_since(field.deployed_at, SLOW_FIELD_LIFETIME) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let mut players: Vec<_> = players.iter_mut().collect();
+    // spawn order has to match between peers
+    players.sort_by_key(|(_, player, ..)| player.id);
+    for (transform, player, team, cooldown) in players.iter_mut() {
+        if InputLayout::SlowField.is_set(inputs[player.id].0.wasd)
+            && ready(cooldown, frame.0)
+            && energy.as_mut().is_none_or(|e| e.spend(**team, SLOW_FIELD_ENERGY))
+        {
+            cooldown.trigger(frame.0);
+            commands.spawn(slow_field(player.id, transform.translation.truncate(), frame.0)).add_rollback();
+        }
+    }
+}
+
+pub fn add_slow_field_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(Entity, &SlowField), Without<Mesh2dHandle>>
+) {
+    for (entity, field) in query.iter() {
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(Rectangle::from_size(field.zone.half_size * 2.))),
+            materials.add(Color::srgba(0.3, 0.5, 0.9, 0.3)),
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::{spawn_rollback, Harness};
+    use crate::collision::CollisionLayer;
+    use crate::items::ItemKind;
+    use crate::script::InputScript;
+    use crate::{PLAYER_SPEED, SPU};
+
+    #[test]
+    fn a_held_magnet_pulls_a_pickup_in_range_toward_the_player() {
+        assert_eq!(magnet_pull(Vec2::new(-100., 0.)), Vec2::new(-MAGNET_PULL / 2., 0.));
+        assert_eq!(magnet_pull(Vec2::new(0., MAGNET_RANGE)), Vec2::ZERO);
+
+        // held on the first frame only
+        let mut harness = Harness::new(1, InputScript::parse("1 0 m\n2 0 -").unwrap());
+        let player = harness.player(0);
+        let world = harness.app.world_mut();
+        let center = world.get::<Transform>(player).unwrap().translation.truncate();
+        let start = center + Vec2::new(100., 0.);
+        let item = spawn_rollback(world, (Item { kind: ItemKind::Heal, spot: 0 }, Transform::from_translation(start.extend(0.5))));
+        let item_pos = |harness: &Harness| harness.app.world().get::<Transform>(item).unwrap().translation.truncate();
+
+        harness.run_to(1);
+        assert_eq!(item_pos(&harness), start - Vec2::new(MAGNET_PULL / 2., 0.));
+        assert_eq!(harness.app.world().get::<Magnet>(player).unwrap().energy, MAGNET_ENERGY - 1);
+        harness.run_to(5);
+        assert_eq!(item_pos(&harness), start - Vec2::new(MAGNET_PULL / 2., 0.));
+    }
+
+    #[test]
+    fn the_hook_sticks_to_the_nearest_wall_and_reels_the_player_in() {
+        let near = (Vec2::new(200., 0.), Vec2::new(10., 100.));
+        let far = (Vec2::new(-280., 0.), Vec2::new(10., 100.));
+        assert_eq!(grapple_anchor(Vec2::ZERO, &[far, near]), Some(Vec2::new(190., 0.)));
+        // equally near, the lower x wins whichever comes first
+        let mirrored = (Vec2::new(-200., 0.), Vec2::new(10., 100.));
+        assert_eq!(grapple_anchor(Vec2::ZERO, &[near, mirrored]), Some(Vec2::new(-190., 0.)));
+        assert_eq!(grapple_anchor(Vec2::new(0., 500.), &[near]), None);
+
+        let mut harness = Harness::new(1, InputScript::parse("1 0 h").unwrap());
+        let player = harness.player(0);
+        let world = harness.app.world_mut();
+        world.get_mut::<Transform>(player).unwrap().translation = Vec3::ZERO;
+        for (center, half_size) in [near, far] {
+            world.spawn((Transform::from_translation(center.extend(0.)), Wall::solid(half_size), CollisionLayer::wall()));
+        }
+        let mut x = 0.;
+        for frame in 1..=10 {
+            harness.run_to(frame);
+            assert_eq!(harness.app.world().get::<Grapple>(player).unwrap().anchor, Some(Vec2::new(190., 0.)));
+            let (_, pos, _) = harness.players()[0];
+            assert!((pos.x - (x + GRAPPLE_PULL * SPU)).abs() < 1e-3, "frame {frame}: {pos}");
+            assert_eq!(pos.y, 0.);
+            x = pos.x;
+        }
+    }
+
+    #[test]
+    fn a_deployed_field_slows_others_inside_it_until_it_expires() {
+        let script = InputScript::parse("1 0 s\n2 0 r\n1 1 u").unwrap();
+        let mut harness = Harness::new(2, script);
+        for (id, x) in [(0, 0.), (1, -60.)] {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(x, 0., 0.);
+        }
+        let fields = |harness: &mut Harness| harness.app.world_mut().query::<&SlowField>().iter(harness.app.world()).count();
+        harness.run_to(10);
+        let players = harness.players();
+        // the owner isn't slowed by its own field
+        assert_eq!(players[0].2.x, PLAYER_SPEED);
+        assert_eq!(players[1].2.y, PLAYER_SPEED * SLOW_FIELD_FACTOR);
+
+        harness.run_to(SLOW_FIELD_LIFETIME);
+        assert_eq!(fields(&mut harness), 1);
+        harness.run_to(SLOW_FIELD_LIFETIME + 1);
+        assert_eq!(fields(&mut harness), 0);
+    }
+
+    #[test]
+    fn a_frozen_player_cant_hook_a_wall() {
+        // player 0 is the tagger, standing well off
+        let mut harness = Harness::new(2, InputScript::parse("1 1 h").unwrap());
+        harness.app.insert_resource(crate::modes::FreezeTag);
+        let (tagger, runner) = (harness.player(0), harness.player(1));
+        let world = harness.app.world_mut();
+        world.get_mut::<Transform>(tagger).unwrap().translation = Vec3::new(-400., 0., 0.);
+        world.get_mut::<Transform>(runner).unwrap().translation = Vec3::ZERO;
+        world.get_mut::<Tagged>(runner).unwrap().frozen = true;
+        world.spawn((Transform::from_xyz(200., 0., 0.), Wall::solid(Vec2::new(10., 100.)), CollisionLayer::wall()));
+        harness.run_to(10);
+        assert!(harness.app.world().get::<Tagged>(runner).unwrap().frozen);
+        assert_eq!(harness.app.world().get::<Grapple>(runner).unwrap().anchor, None);
+        let (_, pos, vel) = harness.players()[1];
+        assert_eq!((pos, vel.x, vel.y), (Vec2::ZERO, 0., 0.));
+    }
+}
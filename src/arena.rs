@@ -0,0 +1,556 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::window::WindowResized;
+use clap::ValueEnum;
+use crate::collision::{CollisionLayer, Wall, SIDES_ALL};
+use crate::map::MapSpec;
+use crate::modes::Sumo;
+use crate::rng::{mix, SimRng};
+use crate::sim::{ready, Cooldown, FrameCounter, SimEvent, SimEvents};
+use bevy_ggrs::Rollback;
+use crate::collision::Knockback;
+use crate::projectile::Projectile;
+use crate::{Player, RollbackPlayer, PLAYER_RADIUS};
+
+const WALL_THICKNESS: f32 = 20.;
+// frames before a player that just came out of a portal can use one again
+pub const PORTAL_COOLDOWN: u32 = 60;
+pub const PAD_COOLDOWN: u32 = 20;
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Arena {
+    pub half_size: Vec2,
+}
+
+// keeps the spawn stream apart from the bots, which use the same seed
+const SPAWN_SEED_KEY: u64 = 0x53_5041_574E;
+const SPAWN_ATTEMPTS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpawnLayout {
+    #[default]
+    Row,
+    Random,
+}
+
+// where a player starts, and comes back after being eliminated
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SpawnPoint(pub Vec2);
+
+pub fn row_spawn(id: usize, players_num: usize) -> Vec2 {
+    Vec2::new((100isize - (100isize * players_num as isize) + (200isize * id as isize)) as f32, 0.)
+}
+
+// Rejection sampling from the shared seed, so every peer places everyone the same. A
+// crowded arena that runs out of attempts keeps the last candidate even if it overlaps.
+pub fn random_spawns(seed: u64, players_num: usize, half_size: Vec2) -> Vec<Vec2> {
+    let mut rng = SimRng::new(mix(seed, SPAWN_SEED_KEY));
+    let area = (half_size - Vec2::splat(PLAYER_RADIUS)).max(Vec2::ZERO);
+    let mut spawns: Vec<Vec2> = Vec::with_capacity(players_num);
+    for _ in 0..players_num {
+        let mut pos = Vec2::ZERO;
+        for _ in 0..SPAWN_ATTEMPTS {
+            pos = Vec2::new(rng.next_f32() * 2. - 1., rng.next_f32() * 2. - 1.) * area;
+            if spawns.iter().all(|other| other.distance(pos) >= 2. * PLAYER_RADIUS) {
+                break;
+            }
+        }
+        spawns.push(pos);
+    }
+    spawns
+}
+
+pub fn spawn_positions(layout: SpawnLayout, seed: u64, players_num: usize, half_size: Vec2) -> Vec<Vec2> {
+    match layout {
+        SpawnLayout::Row => (0..players_num).map(|id| row_spawn(id, players_num)).collect(),
+        SpawnLayout::Random => random_spawns(seed, players_num, half_size),
+    }
+}
+
+// Wrap-around arena: no outer walls, and whatever leaves by one edge comes back in by the
+// opposite one. Distances then have to go the short way around, see `toroidal_offset`.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Wrap;
+
+// shortest offset from `a` to `b` in an arena of `half_size` that wraps around
+pub fn toroidal_offset(a: Vec2, b: Vec2, half_size: Vec2) -> Vec2 {
+    let size = half_size * 2.;
+    let d = b - a;
+    d - size * (d / size).round()
+}
+
+pub fn toroidal_distance(a: Vec2, b: Vec2, half_size: Vec2) -> f32 {
+    toroidal_offset(a, b, half_size).length()
+}
+
+// `toroidal_offset` when wrapping, a plain difference otherwise
+pub fn offset(a: Vec2, b: Vec2, wrap: Option<Vec2>) -> Vec2 {
+    wrap.map_or(b - a, |half_size| toroidal_offset(a, b, half_size))
+}
+
+pub fn distance(a: Vec2, b: Vec2, wrap: Option<Vec2>) -> f32 {
+    wrap.map_or(a.distance(b), |half_size| toroidal_distance(a, b, half_size))
+}
+
+// `players` is (entity, handle, position) in any order, equally near players go to the
+// lowest handle so every peer picks the same one
+pub fn nearest_player(from: Vec2, players: &[(Entity, usize, Vec2)], wrap: Option<Vec2>) -> Option<Entity> {
+    players
+        .iter()
+        .map(|(entity, id, pos)| (*entity, *id, offset(from, *pos, wrap).length_squared()))
+        .min_by(|a, b| a.2.total_cmp(&b.2).then(a.1.cmp(&b.1)))
+        .map(|(entity, _, _)| entity)
+}
+
+// the candidate whose nearest opponent is farthest away, equally safe ones go to the
+// earliest candidate, None without any opponents
+pub fn safest_spawn(candidates: &[Vec2], opponents: &[(Entity, usize, Vec2)], wrap: Option<Vec2>) -> Option<Vec2> {
+    candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &candidate)| {
+            let nearest = nearest_player(candidate, opponents, wrap)?;
+            let (_, _, pos) = opponents.iter().find(|(e, ..)| *e == nearest)?;
+            Some((i, candidate, distance(candidate, *pos, wrap)))
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2).then(b.0.cmp(&a.0)))
+        .map(|(_, candidate, _)| candidate)
+}
+
+pub fn wrap_position(pos: Vec2, half_size: Vec2) -> Vec2 {
+    let size = half_size * 2.;
+    pos - size * ((pos + half_size) / size).floor()
+}
+
+type Wrapped = (With<Rollback>, Or<(With<Player>, With<Projectile>)>);
+
+pub fn wrap_positions(
+    mut query: Query<&mut Transform, Wrapped>,
+    arena: Res<Arena>
+) {
+    for mut transform in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        if pos.abs().cmpgt(arena.half_size).any() {
+            transform.translation = wrap_position(pos, arena.half_size).extend(transform.translation.z);
+        }
+    }
+}
+
+// index into `wall_layout`
+#[derive(Component)]
+pub struct ArenaWall(usize);
+
+// the four boundary walls, as (center, half size)
+pub fn wall_layout(half: Vec2) -> [(Vec2, Vec2); 4] {
+    let t = WALL_THICKNESS / 2.;
+    [
+        (Vec2::new(-half.x - t, 0.), Vec2::new(t, half.y + 2. * t)),
+        (Vec2::new(half.x + t, 0.), Vec2::new(t, half.y + 2. * t)),
+        (Vec2::new(0., -half.y - t), Vec2::new(half.x, t)),
+        (Vec2::new(0., half.y + t), Vec2::new(half.x, t)),
+    ]
+}
+
+pub fn setup_arena(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    arena: Res<Arena>,
+    map: Res<MapSpec>,
+    wrap: Option<Res<Wrap>>,
+    sumo: Option<Res<Sumo>>
+) {
+    let material = materials.add(Color::srgb(0.4, 0.4, 0.4));
+    let one_way_material = materials.add(Color::srgb(0.55, 0.5, 0.3));
+    let moving_material = materials.add(Color::srgb(0.35, 0.45, 0.6));
+
+    let outer_walls = if wrap.is_some() || sumo.is_some() { Vec::new() } else { wall_layout(arena.half_size).to_vec() };
+    for (i, (pos, half_size)) in outer_walls.into_iter().enumerate() {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(half_size * 2.))),
+                material: material.clone(),
+                transform: Transform::from_translation(pos.extend(0.)),
+                ..default()
+            },
+            Wall::solid(half_size),
+            ArenaWall(i),
+            CollisionLayer::wall(),
+        ));
+    }
+
+    for wall in &map.walls {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(wall.half_size * 2.))),
+                material: if wall.sides == SIDES_ALL { material.clone() } else { one_way_material.clone() },
+                transform: Transform::from_translation(wall.center.extend(0.)),
+                ..default()
+            },
+            Wall { half_size: wall.half_size, sides: wall.sides },
+            CollisionLayer::wall(),
+        ));
+    }
+
+    for mover in &map.movers {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(mover.half_size * 2.))),
+                material: moving_material.clone(),
+                transform: Transform::from_translation(mover.position_at(0).extend(0.)),
+                ..default()
+            },
+            Wall::solid(mover.half_size),
+            *mover,
+            CollisionLayer::wall(),
+        ));
+    }
+}
+
+// Obstacle going back and forth between `a` and `b`, taking `period` frames each way.
+// Its position is a function of the frame alone, so it needs no rollback state.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct MovingWall {
+    pub a: Vec2,
+    pub b: Vec2,
+    pub half_size: Vec2,
+    pub period: u32,
+}
+
+impl MovingWall {
+    pub fn position_at(&self, frame: u32) -> Vec2 {
+        let t = frame % (2 * self.period);
+        let step = if t < self.period { t } else { 2 * self.period - t };
+        self.a.lerp(self.b, step as f32 / self.period as f32)
+    }
+}
+
+// runs before wall collisions, which push out whoever it moved into
+pub fn move_walls(mut walls: Query<(&mut Transform, &MovingWall)>, frame: Res<FrameCounter>) {
+    for (mut transform, mover) in walls.iter_mut() {
+        transform.translation = mover.position_at(frame.0).extend(transform.translation.z);
+    }
+}
+
+// Window-sized arena. Resizing is local, so it can't reach the simulation directly: every
+// resize is stamped with the next frame to simulate and the arena for a frame is looked up
+// from these stamps. Re-simulating a frame then sees the same walls as the first run.
+// Remote peers never see our window, so this is only enabled without any (SyncTest).
+#[derive(Resource, Default)]
+pub struct ArenaResizes(pub Vec<(u32, Vec2)>);
+
+impl ArenaResizes {
+    pub fn half_size_at(&self, frame: u32) -> Option<Vec2> {
+        let next = self.0.partition_point(|(at, _)| *at <= frame);
+        next.checked_sub(1).map(|i| self.0[i].1)
+    }
+}
+
+pub fn queue_arena_resize(
+    mut events: EventReader<WindowResized>,
+    mut resizes: ResMut<ArenaResizes>,
+    frame: Res<FrameCounter>
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let half = (Vec2::new(event.width, event.height) / 2. - WALL_THICKNESS).max(Vec2::ZERO);
+    let at = frame.0 + 1;
+    if resizes.0.last().is_some_and(|(last, _)| *last == at) {
+        resizes.0.pop();
+    }
+    resizes.0.push((at, half));
+}
+
+pub fn apply_arena_resizes(
+    resizes: Res<ArenaResizes>,
+    frame: Res<FrameCounter>,
+    mut arena: ResMut<Arena>,
+    mut walls: Query<(&mut Transform, &mut Wall, &ArenaWall), Without<Player>>
+) {
+    let Some(half) = resizes.half_size_at(frame.0) else {
+        return;
+    };
+    if half == arena.half_size {
+        return;
+    }
+    arena.half_size = half;
+    let layout = wall_layout(half);
+    for (mut transform, mut wall, ArenaWall(i)) in walls.iter_mut() {
+        let (pos, half_size) = layout[*i];
+        transform.translation = pos.extend(transform.translation.z);
+        wall.half_size = half_size;
+    }
+}
+
+pub fn resize_wall_meshes(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(&Wall, &mut Mesh2dHandle), Changed<Wall>>
+) {
+    for (wall, mut mesh) in query.iter_mut() {
+        *mesh = Mesh2dHandle(meshes.add(Rectangle::from_size(wall.half_size * 2.)));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortalEnd {
+    pub pos: Vec2,
+    pub exit: Vec2,
+    pub radius: f32,
+}
+
+// in map order, which is the same on every peer
+#[derive(Resource, Default)]
+pub struct Portals(pub Vec<PortalEnd>);
+
+impl Portals {
+    pub fn from_map(map: &MapSpec) -> Self {
+        let ends = map.portals.iter().flat_map(|p| {
+            [
+                PortalEnd { pos: p.a, exit: p.b, radius: p.radius },
+                PortalEnd { pos: p.b, exit: p.a, radius: p.radius },
+            ]
+        });
+        Portals(ends.collect())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PortalUse;
+
+pub fn portal_exit(pos: Vec2, portals: &[PortalEnd]) -> Option<Vec2> {
+    portals.iter().find(|p| pos.distance(p.pos) < p.radius).map(|p| p.exit)
+}
+
+// velocity is left alone, players keep moving the way they went in
+pub fn teleport_players(
+    mut query: Query<(&mut Transform, &mut Cooldown<PortalUse>, &Player), RollbackPlayer>,
+    portals: Res<Portals>,
+    frame: Res<FrameCounter>,
+    mut events: ResMut<SimEvents>
+) {
+    let mut players: Vec<_> = query.iter_mut().collect();
+    // events have to come out in the same order on every peer
+    players.sort_by_key(|(.., player)| player.id);
+    for (transform, cooldown, player) in players.iter_mut() {
+        if !ready(cooldown, frame.0) {
+            continue;
+        }
+        if let Some(exit) = portal_exit(transform.translation.truncate(), &portals.0) {
+            transform.translation = exit.extend(transform.translation.z);
+            cooldown.trigger(frame.0);
+            events.0.push(SimEvent::Relocated { player: player.id, to: exit });
+        }
+    }
+}
+
+pub fn draw_portals(mut gizmos: Gizmos, portals: Res<Portals>) {
+    for portal in &portals.0 {
+        gizmos.circle_2d(portal.pos, portal.radius, Color::srgb(0., 0.8, 1.));
+    }
+}
+
+// Launches players touching it. Velocity comes from input every frame, so the launch is
+// handed over as knockback, which the player keeps for a while as it decays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BouncePad {
+    pub center: Vec2,
+    pub radius: f32,
+    pub launch: Vec2,
+}
+
+pub fn touched_pad(pos: Vec2, pads: &[BouncePad]) -> Option<&BouncePad> {
+    pads.iter().find(|pad| pos.distance(pad.center) < pad.radius + PLAYER_RADIUS)
+}
+
+pub fn bounce_players(
+    mut query: Query<(&Transform, &mut Knockback, &mut Cooldown<BouncePad>), RollbackPlayer>,
+    map: Res<MapSpec>,
+    frame: Res<FrameCounter>
+) {
+    for (transform, mut knockback, mut cooldown) in query.iter_mut() {
+        if !ready(&cooldown, frame.0) {
+            continue;
+        }
+        if let Some(pad) = touched_pad(transform.translation.truncate(), &map.pads) {
+            knockback.0 = pad.launch;
+            cooldown.trigger(frame.0);
+        }
+    }
+}
+
+pub fn draw_bounce_pads(mut gizmos: Gizmos, map: Res<MapSpec>) {
+    for pad in &map.pads {
+        let color = Color::srgb(0.2, 1., 0.4);
+        gizmos.circle_2d(pad.center, pad.radius, color);
+        gizmos.arrow_2d(pad.center, pad.center + pad.launch.normalize_or_zero() * pad.radius, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+    use crate::harness::{spawn_rollback, Harness};
+    use crate::map::PortalPair;
+    use crate::script::InputScript;
+    use crate::PLAYER_SPEED;
+
+    #[test]
+    fn a_portal_keeps_velocity_and_cools_down() {
+        let map = MapSpec {
+            portals: vec![PortalPair { a: Vec2::new(40., 0.), b: Vec2::new(-300., 0.), radius: 30. }],
+            ..default()
+        };
+        let mut harness = Harness::new(1, InputScript::parse("1 0 r").unwrap());
+        harness.app.insert_resource(Portals::from_map(&map));
+        let player = harness.player(0);
+        harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::ZERO;
+
+        let mut frame = 0;
+        while harness.players()[0].1.x >= 0. {
+            frame += 1;
+            harness.run_to(frame);
+        }
+        let (_, pos, vel) = harness.players()[0];
+        assert_eq!(pos, Vec2::new(-300., 0.));
+        assert_eq!((vel.x, vel.y), (PLAYER_SPEED, 0.));
+
+        // still standing in the far end, which would send it right back if not for the cooldown
+        harness.run_to(frame + 5);
+        let (_, pos, vel) = harness.players()[0];
+        assert!(pos.distance(Vec2::new(-300., 0.)) < 30., "{pos}");
+        assert_eq!((vel.x, vel.y), (PLAYER_SPEED, 0.));
+    }
+
+    #[test]
+    fn a_window_resize_reaches_the_arena_on_the_next_frame() {
+        let mut world = World::new();
+        world.init_resource::<Events<WindowResized>>();
+        world.init_resource::<ArenaResizes>();
+        world.insert_resource(Arena { half_size: Vec2::new(600., 340.) });
+        world.insert_resource(FrameCounter(5));
+        world.send_event(WindowResized { window: Entity::PLACEHOLDER, width: 800., height: 600. });
+        world.run_system_once(queue_arena_resize);
+
+        world.run_system_once(apply_arena_resizes);
+        assert_eq!(world.resource::<Arena>().half_size, Vec2::new(600., 340.));
+        world.resource_mut::<FrameCounter>().0 = 6;
+        world.run_system_once(apply_arena_resizes);
+        let half = Vec2::new(400., 300.) - WALL_THICKNESS;
+        assert_eq!(world.resource::<Arena>().half_size, half);
+
+        // rolled back to before the resize, frame 5 still gets the old size
+        let resizes = world.resource::<ArenaResizes>();
+        assert_eq!(resizes.half_size_at(5), None);
+        assert_eq!(resizes.half_size_at(100), Some(half));
+    }
+
+    #[test]
+    fn a_moving_wall_ping_pongs_and_shoves_players_out_of_its_way() {
+        let mover = MovingWall { a: Vec2::new(-100., 0.), b: Vec2::new(100., 0.), half_size: Vec2::new(10., 100.), period: 100 };
+        assert_eq!(mover.position_at(0), mover.a);
+        assert_eq!(mover.position_at(50), Vec2::ZERO);
+        assert_eq!(mover.position_at(100), mover.b);
+        assert_eq!(mover.position_at(150), Vec2::ZERO);
+        assert_eq!(mover.position_at(200), mover.a);
+        assert_eq!(mover.position_at(260), mover.position_at(60));
+
+        let mut harness = Harness::new(1, InputScript::default());
+        let player = harness.player(0);
+        let world = harness.app.world_mut();
+        world.get_mut::<Transform>(player).unwrap().translation = Vec3::ZERO;
+        world.spawn((Transform::from_translation(mover.a.extend(0.)), Wall::solid(mover.half_size), mover, CollisionLayer::wall()));
+        harness.run_to(60);
+        let (_, pos, _) = harness.players()[0];
+        // standing still, but pushed along in front of the wall's right face
+        assert!((pos.x - (mover.position_at(60).x + 10. + PLAYER_RADIUS)).abs() < 1e-3, "{pos}");
+    }
+
+    #[test]
+    fn random_spawns_repeat_per_seed_and_never_overlap() {
+        let half = Vec2::new(600., 340.);
+        let spawns = random_spawns(42, 16, half);
+        assert_eq!(spawns, random_spawns(42, 16, half));
+        assert_ne!(spawns, random_spawns(43, 16, half));
+        for (i, a) in spawns.iter().enumerate() {
+            assert!(a.abs().cmple(half - PLAYER_RADIUS).all(), "{a} is outside the arena");
+            for b in &spawns[i + 1..] {
+                assert!(a.distance(*b) >= 2. * PLAYER_RADIUS, "{a} overlaps {b}");
+            }
+        }
+        assert_eq!(spawn_positions(SpawnLayout::Random, 42, 16, half), spawns);
+    }
+
+    #[test]
+    fn a_bounce_pad_launches_a_player_once_per_cooldown() {
+        let pad = BouncePad { center: Vec2::ZERO, radius: 30., launch: Vec2::new(0., 600.) };
+        let mut world = World::new();
+        world.insert_resource(MapSpec { pads: vec![pad], ..default() });
+        world.insert_resource(FrameCounter(1));
+        let player = spawn_rollback(&mut world, (
+            Player { id: 0 },
+            Transform::from_xyz(PLAYER_RADIUS + 20., 0., 0.),
+            Knockback::default(),
+            Cooldown::<BouncePad>::new(PAD_COOLDOWN),
+        ));
+        let mut launched = Vec::new();
+        for frame in 1..=PAD_COOLDOWN + 1 {
+            world.resource_mut::<FrameCounter>().0 = frame;
+            world.run_system_once(bounce_players);
+            let mut knockback = world.get_mut::<Knockback>(player).unwrap();
+            if knockback.0 != Vec2::ZERO {
+                assert_eq!(knockback.0, pad.launch);
+                launched.push(frame);
+                knockback.0 = Vec2::ZERO;
+            }
+        }
+        // still standing on it when the cooldown runs out
+        assert_eq!(launched, [1, 1 + PAD_COOLDOWN]);
+        assert_eq!(touched_pad(Vec2::new(PLAYER_RADIUS + 30., 0.), &[pad]), None);
+    }
+
+    #[test]
+    fn near_opposite_edges_the_short_way_goes_around() {
+        let half = Vec2::new(600., 340.);
+        let (a, b) = (Vec2::new(590., 330.), Vec2::new(-590., -330.));
+        assert!((a.distance(b) - Vec2::new(1180., 660.).length()).abs() < 1e-3);
+        assert!((toroidal_distance(a, b, half) - Vec2::new(20., 20.).length()).abs() < 1e-3);
+        assert_eq!(toroidal_offset(a, b, half), Vec2::new(20., 20.));
+        assert_eq!(toroidal_offset(b, a, half), Vec2::new(-20., -20.));
+        // nowhere near an edge, both agree
+        let (c, d) = (Vec2::new(-100., 50.), Vec2::new(200., -10.));
+        assert_eq!(toroidal_distance(c, d, half), c.distance(d));
+        assert_eq!(offset(c, d, None), offset(c, d, Some(half)));
+    }
+
+    #[test]
+    fn equidistant_players_go_to_the_lowest_id_whatever_the_order() {
+        let [a, b, c] = [Entity::from_raw(7), Entity::from_raw(3), Entity::from_raw(5)];
+        // ids 2 and 1 are both 100 away, id 0 is farther
+        let players = [(a, 2, Vec2::new(100., 0.)), (b, 1, Vec2::new(0., -100.)), (c, 0, Vec2::new(150., 0.))];
+        assert_eq!(nearest_player(Vec2::ZERO, &players, None), Some(b));
+        let mut reversed = players;
+        reversed.reverse();
+        assert_eq!(nearest_player(Vec2::ZERO, &reversed, None), Some(b));
+        assert_eq!(nearest_player(Vec2::ZERO, &players[..1], None), Some(a));
+        assert_eq!(nearest_player(Vec2::ZERO, &[], None), None);
+    }
+
+    #[test]
+    fn respawns_at_the_spawn_farthest_from_the_closest_opponent() {
+        let candidates = [Vec2::new(-500., 250.), Vec2::new(500., 250.), Vec2::new(-500., -250.), Vec2::new(500., -250.)];
+        let opponent = |id, x, y| (Entity::from_raw(id as u32), id, Vec2::new(x, y));
+        // two camping the top left and one at the bottom middle leave the top right safest
+        let campers = [opponent(1, -480., 240.), opponent(2, -450., 200.), opponent(3, 0., -200.)];
+        assert_eq!(safest_spawn(&candidates, &campers, None), Some(candidates[1]));
+        let mut reversed = campers;
+        reversed.reverse();
+        assert_eq!(safest_spawn(&candidates, &reversed, None), Some(candidates[1]));
+
+        // equally safe spawns go to the first one listed
+        let center = [opponent(1, 0., 0.)];
+        assert_eq!(safest_spawn(&candidates, &center, None), Some(candidates[0]));
+        assert_eq!(safest_spawn(&candidates, &[], None), None);
+    }
+}
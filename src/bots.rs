@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use clap::ValueEnum;
+use crate::arena::{nearest_player, offset};
+use crate::rng::{mix, SimRng};
+use crate::input::{InputLayout, InputPacked};
+
+// how long a bot keeps going in one direction
+const BOT_DECISION_FRAMES: i32 = 30;
+// an axis only counts once the target is this far off it, so chasing bots don't jitter
+const BOT_AXIS_DEADZONE: f32 = 10.;
+
+const BOT_MOVES: [u8; 9] = [
+    0,
+    InputLayout::Up.mask(),
+    InputLayout::Down.mask(),
+    InputLayout::Right.mask(),
+    InputLayout::Left.mask(),
+    InputLayout::Up.mask() | InputLayout::Right.mask(),
+    InputLayout::Up.mask() | InputLayout::Left.mask(),
+    InputLayout::Down.mask() | InputLayout::Right.mask(),
+    InputLayout::Down.mask() | InputLayout::Left.mask(),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BotBehavior {
+    #[default]
+    Wander,
+    Chase,
+    Flee,
+}
+
+#[derive(Resource, Default)]
+pub struct Bots {
+    pub seed: u64,
+    pub handles: Vec<usize>,
+    pub behavior: BotBehavior,
+    // arena half size if it wraps around, distances to other players go the short way then
+    pub wrap: Option<Vec2>,
+}
+
+impl Bots {
+    pub fn controls(&self, handle: usize) -> bool {
+        self.handles.contains(&handle)
+    }
+}
+
+// a pure function of seed, handle and frame, so it doesn't matter how often it's called
+pub fn wander_input(seed: u64, handle: usize, frame: i32) -> InputPacked {
+    let decision = frame.max(0) / BOT_DECISION_FRAMES;
+    let mut rng = SimRng::new(mix(mix(seed, handle as u64), decision as u64));
+    let wasd = BOT_MOVES[(rng.next_u64() % BOT_MOVES.len() as u64) as usize];
+    InputPacked::new(wasd)
+}
+
+pub fn direction_bits(dir: Vec2) -> u8 {
+    let mut wasd = 0;
+    if dir.x > BOT_AXIS_DEADZONE {
+        wasd |= InputLayout::Right.mask();
+    } else if dir.x < -BOT_AXIS_DEADZONE {
+        wasd |= InputLayout::Left.mask();
+    }
+    if dir.y > BOT_AXIS_DEADZONE {
+        wasd |= InputLayout::Up.mask();
+    } else if dir.y < -BOT_AXIS_DEADZONE {
+        wasd |= InputLayout::Down.mask();
+    }
+    wasd
+}
+
+// `players` is (entity, handle, position) of everyone, see `nearest_player`
+pub fn bot_input(bots: &Bots, handle: usize, frame: i32, players: &[(Entity, usize, Vec2)]) -> InputPacked {
+    let me = players.iter().find(|(_, id, _)| *id == handle).map(|(_, _, pos)| *pos);
+    let nearest = me.and_then(|me| {
+        let others: Vec<_> = players.iter().filter(|(_, id, _)| *id != handle).copied().collect();
+        let target = nearest_player(me, &others, bots.wrap)?;
+        others.iter().find(|(e, _, _)| *e == target).map(|(_, _, pos)| offset(me, *pos, bots.wrap))
+    });
+    match (bots.behavior, nearest) {
+        (BotBehavior::Chase, Some(offset)) => InputPacked::new(direction_bits(offset)),
+        (BotBehavior::Flee, Some(offset)) => InputPacked::new(direction_bits(-offset)),
+        _ => wander_input(bots.seed, handle, frame),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wander_is_a_pure_function_of_seed_handle_and_frame() {
+        let run = |seed| (0..600).map(|frame| wander_input(seed, 1, frame).wasd).collect::<Vec<_>>();
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+        // one decision holds for a whole stretch of frames
+        assert_eq!(wander_input(42, 1, 30), wander_input(42, 1, 59));
+        assert_ne!(
+            (0..600).map(|frame| wander_input(42, 0, frame).wasd).collect::<Vec<_>>(),
+            run(42),
+            "handles wander on their own"
+        );
+    }
+
+    #[test]
+    fn each_behavior_moves_the_expected_way() {
+        let players = [
+            (Entity::from_raw(0), 0, Vec2::new(100., 50.)),
+            (Entity::from_raw(1), 1, Vec2::ZERO),
+            (Entity::from_raw(2), 2, Vec2::new(-300., 0.)),
+        ];
+        let bots = |behavior| Bots { seed: 7, handles: vec![1], behavior, wrap: None };
+        let toward = InputLayout::Right.mask() | InputLayout::Up.mask();
+        let away = InputLayout::Left.mask() | InputLayout::Down.mask();
+        assert_eq!(bot_input(&bots(BotBehavior::Chase), 1, 10, &players).wasd, toward);
+        assert_eq!(bot_input(&bots(BotBehavior::Flee), 1, 10, &players).wasd, away);
+        assert_eq!(bot_input(&bots(BotBehavior::Wander), 1, 10, &players), wander_input(7, 1, 10));
+        // alone in the arena there's nobody to chase
+        assert_eq!(bot_input(&bots(BotBehavior::Chase), 1, 10, &players[1..2]), wander_input(7, 1, 10));
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use bevy_ggrs::LocalPlayers;
+use crate::arena::{nearest_player, offset, Arena, Wrap};
+use crate::bots::Bots;
+use crate::teams::Team;
+use crate::Player;
+
+// how far inside the screen edge the arrows sit, in world units
+const INDICATOR_MARGIN: f32 = 24.;
+const INDICATOR_SIZE: f32 = 12.;
+
+#[derive(Resource)]
+pub struct CameraFollow;
+
+#[derive(Resource)]
+pub struct OffscreenIndicators;
+
+#[derive(Resource)]
+pub struct NearestIndicator;
+
+// the first human handle on this machine, or the first bot if there are only bots
+fn followed_handle(local: &LocalPlayers, bots: &Bots) -> Option<usize> {
+    let humans = local.0.iter().filter(|id| !bots.controls(**id)).min();
+    humans.or(local.0.iter().min()).copied()
+}
+
+// render-only, the camera is never part of the simulation
+pub fn follow_local_player(
+    mut cameras: Query<&mut Transform, (With<Camera2d>, Without<Player>)>,
+    players: Query<(&Transform, &Player)>,
+    local: Res<LocalPlayers>,
+    bots: Res<Bots>
+) {
+    let Some(handle) = followed_handle(&local, &bots) else {
+        return;
+    };
+    let Some((target, _)) = players.iter().find(|(_, p)| p.id == handle) else {
+        return;
+    };
+    for mut camera in cameras.iter_mut() {
+        camera.translation.x = target.translation.x;
+        camera.translation.y = target.translation.y;
+    }
+}
+
+// For a point outside the view, with `offset` from the view center, the spot on the
+// inset view edge along the line toward it and the angle pointing at it.
+pub fn edge_indicator(offset: Vec2, half_view: Vec2) -> Option<(Vec2, f32)> {
+    if offset.x.abs() <= half_view.x && offset.y.abs() <= half_view.y {
+        return None;
+    }
+    let inset = (half_view - INDICATOR_MARGIN).max(Vec2::ONE);
+    // shrink the offset until it touches the inset box
+    let scale = (inset.x / offset.x.abs()).min(inset.y / offset.y.abs());
+    Some((offset * scale, offset.y.atan2(offset.x)))
+}
+
+pub fn draw_offscreen_indicators(
+    mut gizmos: Gizmos,
+    cameras: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    players: Query<(&Transform, &Player)>
+) {
+    let Ok((camera, projection)) = cameras.get_single() else {
+        return;
+    };
+    let center = camera.translation.truncate();
+    let half_view = projection.area.half_size();
+    for (transform, _) in players.iter() {
+        let Some((pos, angle)) = edge_indicator(transform.translation.truncate() - center, half_view) else {
+            continue;
+        };
+        let tip = center + pos;
+        let dir = Vec2::from_angle(angle);
+        let back = tip - dir * INDICATOR_SIZE;
+        let side = dir.perp() * INDICATOR_SIZE / 2.;
+        gizmos.linestrip_2d([tip, back + side, back - side, tip], Color::WHITE);
+    }
+}
+
+// render-only line from the followed player to the nearest one on another team
+pub fn draw_nearest_indicator(
+    mut gizmos: Gizmos,
+    players: Query<(Entity, &Transform, &Player, &Team)>,
+    local: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    arena: Res<Arena>,
+    wrap: Option<Res<Wrap>>
+) {
+    let Some(handle) = followed_handle(&local, &bots) else {
+        return;
+    };
+    let Some((_, me, _, team)) = players.iter().find(|(_, _, p, _)| p.id == handle) else {
+        return;
+    };
+    let from = me.translation.truncate();
+    let wrap = wrap.map(|_| arena.half_size);
+    let opponents: Vec<_> = players
+        .iter()
+        .filter(|(_, _, _, t)| t.0 != team.0)
+        .map(|(e, t, p, _)| (e, p.id, t.translation.truncate()))
+        .collect();
+    let Some(target) = nearest_player(from, &opponents, wrap) else {
+        return;
+    };
+    let Some((_, _, pos)) = opponents.iter().find(|(e, _, _)| *e == target) else {
+        return;
+    };
+    gizmos.line_2d(from, from + offset(from, *pos, wrap), Color::srgba(1., 0.3, 0.3, 0.6));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use super::*;
+
+    #[test]
+    fn off_screen_players_get_an_arrow_on_the_edge_toward_them() {
+        let half_view = Vec2::new(400., 300.);
+        assert_eq!(edge_indicator(Vec2::new(390., -290.), half_view), None);
+
+        let (pos, angle) = edge_indicator(Vec2::new(1000., 0.), half_view).unwrap();
+        assert_eq!((pos, angle), (Vec2::new(400. - INDICATOR_MARGIN, 0.), 0.));
+        let (pos, angle) = edge_indicator(Vec2::new(0., -900.), half_view).unwrap();
+        assert_eq!((pos, angle), (Vec2::new(0., INDICATOR_MARGIN - 300.), -FRAC_PI_2));
+
+        // a corner-ish direction hits whichever inset edge comes first, on the same line
+        let offset = Vec2::new(-600., 552.);
+        let (pos, angle) = edge_indicator(offset, half_view).unwrap();
+        assert_eq!(pos.y, 300. - INDICATOR_MARGIN);
+        assert!(pos.x > -(400. - INDICATOR_MARGIN));
+        assert!(pos.perp_dot(offset).abs() < 1e-2);
+        assert!((Vec2::from_angle(angle) - offset.normalize()).length() < 1e-6);
+    }
+}
@@ -0,0 +1,737 @@
+use std::collections::BTreeMap;
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use clap::ValueEnum;
+use crate::sim::{PrevPosition, SimEvent, SimEvents};
+use crate::teams::Team;
+use crate::{Player, RollbackPlayer, Velocity, PLAYER_RADIUS};
+
+// what a player ran into, the player is always `a` in the event and this is `b`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionKind {
+    Player,
+    Projectile,
+    Wall,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionLayer {
+    // layers this collider belongs to
+    pub memberships: u32,
+    // layers this collider wants to collide with
+    pub filters: u32,
+}
+
+impl CollisionLayer {
+    pub const PLAYER: u32 = 1 << 0;
+    pub const PROJECTILE: u32 = 1 << 1;
+    pub const BALL: u32 = 1 << 2;
+    pub const WALL: u32 = 1 << 3;
+
+    pub const fn new(memberships: u32, filters: u32) -> Self {
+        Self { memberships, filters }
+    }
+
+    pub const fn player() -> Self {
+        Self::new(Self::PLAYER, Self::PLAYER | Self::PROJECTILE | Self::BALL | Self::WALL)
+    }
+
+    pub const fn projectile() -> Self {
+        Self::new(Self::PROJECTILE, Self::PLAYER | Self::WALL)
+    }
+
+    pub const fn wall() -> Self {
+        Self::new(Self::WALL, Self::PLAYER | Self::PROJECTILE)
+    }
+
+    pub fn interacts(&self, other: &CollisionLayer) -> bool {
+        (self.memberships & other.filters) != 0 && (other.memberships & self.filters) != 0
+    }
+}
+
+// heavier bodies get pushed less when resolving overlaps
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Mass(pub f32);
+
+impl Default for Mass {
+    fn default() -> Self {
+        Mass(1.)
+    }
+}
+
+// fractions of the penetration each side moves, summing to one
+pub fn separation_shares(a: Mass, b: Mass) -> (f32, f32) {
+    let total = a.0 + b.0;
+    if total <= 0. {
+        return (0.5, 0.5);
+    }
+    (b.0 / total, a.0 / total)
+}
+
+pub const SIDE_TOP: u8 = 1;
+pub const SIDE_BOTTOM: u8 = 2;
+pub const SIDE_LEFT: u8 = 4;
+pub const SIDE_RIGHT: u8 = 8;
+pub const SIDES_ALL: u8 = SIDE_TOP | SIDE_BOTTOM | SIDE_LEFT | SIDE_RIGHT;
+// slack for a player resting on a one-way face, which ends each frame exactly on it
+const ONE_WAY_TOLERANCE: f32 = 1.;
+
+pub fn sides_from_letters(letters: &str) -> Option<u8> {
+    letters.chars().try_fold(0, |sides, c| {
+        let side = match c {
+            't' => SIDE_TOP,
+            'b' => SIDE_BOTTOM,
+            'l' => SIDE_LEFT,
+            'r' => SIDE_RIGHT,
+            _ => return None,
+        };
+        Some(sides | side)
+    })
+}
+
+// the box face a contact normal points out of, corners go to the dominant axis
+fn side_of(normal: Vec2) -> (u8, Vec2) {
+    if normal.x.abs() > normal.y.abs() {
+        if normal.x < 0. { (SIDE_LEFT, Vec2::NEG_X) } else { (SIDE_RIGHT, Vec2::X) }
+    } else if normal.y < 0. {
+        (SIDE_BOTTOM, Vec2::NEG_Y)
+    } else {
+        (SIDE_TOP, Vec2::Y)
+    }
+}
+
+// static axis-aligned box, centered on its transform
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Wall {
+    pub half_size: Vec2,
+    // faces that block, a player can pass through the others
+    pub sides: u8,
+}
+
+impl Wall {
+    pub fn solid(half_size: Vec2) -> Self {
+        Self { half_size, sides: SIDES_ALL }
+    }
+
+    // A one-way face only blocks a circle that was fully outside it on the previous frame,
+    // so crossing in through an open face doesn't get it pushed out through a solid one.
+    pub fn blocks(&self, normal: Vec2, prev_center: Vec2, box_center: Vec2, radius: f32) -> bool {
+        if self.sides == SIDES_ALL {
+            return true;
+        }
+        let (side, axis) = side_of(normal);
+        if self.sides & side == 0 {
+            return false;
+        }
+        let extent = (self.half_size * axis).abs().max_element();
+        (prev_center - box_center).dot(axis) >= extent + radius - ONE_WAY_TOLERANCE
+    }
+}
+
+// contact normal (pointing out of the box) and penetration depth of a circle overlapping a box
+pub fn circle_box_contact(center: Vec2, radius: f32, box_center: Vec2, half_size: Vec2) -> Option<(Vec2, f32)> {
+    let closest = center.clamp(box_center - half_size, box_center + half_size);
+    let delta = center - closest;
+    let dist = delta.length();
+    if dist >= radius {
+        return None;
+    }
+    if dist > 0. {
+        return Some((delta / dist, radius - dist));
+    }
+    // center is inside the box, leave along the shallowest axis
+    let local = center - box_center;
+    let depth = half_size - local.abs();
+    if depth.x < depth.y {
+        Some((Vec2::new(if local.x < 0. { -1. } else { 1. }, 0.), depth.x + radius))
+    } else {
+        Some((Vec2::new(0., if local.y < 0. { -1. } else { 1. }), depth.y + radius))
+    }
+}
+
+// bounce off a surface, only if moving into it
+pub fn reflect(vel: Vec2, normal: Vec2) -> Vec2 {
+    let into = vel.dot(normal);
+    if into < 0. {
+        vel - normal * 2. * into
+    } else {
+        vel
+    }
+}
+
+// drops the part of the velocity going into the surface, keeps the part along it
+pub fn slide(vel: Vec2, normal: Vec2) -> Vec2 {
+    let into = vel.dot(normal);
+    if into < 0. {
+        vel - normal * into
+    } else {
+        vel
+    }
+}
+
+// pairs (i, j) with i < j whose layers interact, in a stable order given a stable input order
+pub fn candidate_pairs(layers: &[CollisionLayer]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..layers.len() {
+        for j in (i + 1)..layers.len() {
+            if layers[i].interacts(&layers[j]) {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+// below this many colliders building the grid costs more than it saves
+const BROADPHASE_THRESHOLD: usize = 16;
+const GRID_CELL_SIZE: f32 = 100.;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Proxy {
+    pub pos: Vec2,
+    pub radius: f32,
+    pub layer: CollisionLayer,
+}
+
+impl Proxy {
+    pub fn overlaps(&self, other: &Proxy) -> bool {
+        let reach = self.radius + other.radius;
+        self.pos.distance_squared(other.pos) <= reach * reach
+    }
+}
+
+pub fn brute_force_pairs(proxies: &[Proxy]) -> Vec<(usize, usize)> {
+    let layers: Vec<CollisionLayer> = proxies.iter().map(|p| p.layer).collect();
+    candidate_pairs(&layers)
+        .into_iter()
+        .filter(|&(i, j)| proxies[i].overlaps(&proxies[j]))
+        .collect()
+}
+
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: BTreeMap<(i32, i32), Vec<usize>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(GRID_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    fn cell(&self, pos: Vec2) -> (i32, i32) {
+        ((pos.x / self.cell_size).floor() as i32, (pos.y / self.cell_size).floor() as i32)
+    }
+
+    pub fn rebuild(&mut self, proxies: &[Proxy]) {
+        self.cells.clear();
+        for (i, proxy) in proxies.iter().enumerate() {
+            let min = self.cell(proxy.pos - Vec2::splat(proxy.radius));
+            let max = self.cell(proxy.pos + Vec2::splat(proxy.radius));
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    self.cells.entry((x, y)).or_default().push(i);
+                }
+            }
+        }
+    }
+
+    // same pairs and order as brute_force_pairs
+    pub fn pairs(&self, proxies: &[Proxy]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for bucket in self.cells.values() {
+            for (a, &i) in bucket.iter().enumerate() {
+                for &j in &bucket[a + 1..] {
+                    if proxies[i].layer.interacts(&proxies[j].layer) && proxies[i].overlaps(&proxies[j]) {
+                        pairs.push((i, j));
+                    }
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+pub fn broadphase(grid: &mut SpatialGrid, proxies: &[Proxy]) -> Vec<(usize, usize)> {
+    if proxies.len() < BROADPHASE_THRESHOLD {
+        return brute_force_pairs(proxies);
+    }
+    grid.rebuild(proxies);
+    grid.pairs(proxies)
+}
+
+// Velocity a player keeps from being bumped, on top of what its input asks for. It fades
+// out over a few frames since input velocity is recomputed every frame.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Knockback(pub Vec2);
+
+const KNOCKBACK_DECAY: f32 = 0.9;
+
+impl Knockback {
+    // the knockback applied this frame
+    pub fn tick(&mut self) -> Vec2 {
+        let current = self.0;
+        self.0 *= KNOCKBACK_DECAY;
+        current
+    }
+}
+
+// Velocity changes of a 1D elastic collision along `normal` (pointing from a to b),
+// zero if the two are already separating.
+pub fn elastic_exchange(vel_a: Vec2, vel_b: Vec2, mass_a: Mass, mass_b: Mass, normal: Vec2) -> (Vec2, Vec2) {
+    let (va, vb) = (vel_a.dot(normal), vel_b.dot(normal));
+    let total = mass_a.0 + mass_b.0;
+    if va - vb <= 0. || total <= 0. {
+        return (Vec2::ZERO, Vec2::ZERO);
+    }
+    let (ma, mb) = (mass_a.0, mass_b.0);
+    let va_after = (va * (ma - mb) + 2. * mb * vb) / total;
+    let vb_after = (vb * (mb - ma) + 2. * ma * va) / total;
+    (normal * (va_after - va), normal * (vb_after - vb))
+}
+
+// with `Off` teammates pass through each other, they still bump into enemies and walls
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TeamCollision {
+    #[default]
+    On,
+    Off,
+}
+
+impl TeamCollision {
+    pub fn collides(self, a: Team, b: Team) -> bool {
+        self == TeamCollision::On || a != b
+    }
+}
+
+type Body = (
+    Entity,
+    &'static mut Transform,
+    &'static Velocity,
+    &'static mut Knockback,
+    &'static Player,
+    &'static CollisionLayer,
+    &'static Mass,
+    &'static Team,
+);
+
+pub fn resolve_player_collisions(
+    mut grid: Local<SpatialGrid>,
+    mut query: Query<Body, With<Rollback>>,
+    mut events: ResMut<SimEvents>,
+    team_collision: Res<TeamCollision>
+) {
+    let mut bodies: Vec<_> = query.iter_mut().collect();
+    // query order isn't guaranteed to match between peers
+    bodies.sort_by_key(|(_, _, _, _, player, ..)| player.id);
+
+    let proxies: Vec<Proxy> = bodies
+        .iter()
+        .map(|(_, transform, _, _, _, layer, ..)| Proxy {
+            pos: transform.translation.truncate(),
+            radius: PLAYER_RADIUS,
+            layer: **layer,
+        })
+        .collect();
+    let mut pushes = vec![Vec2::ZERO; bodies.len()];
+    // every pair sees the velocities from before any exchange, so pair order doesn't matter
+    let mut bumps = vec![Vec2::ZERO; bodies.len()];
+
+    for (i, j) in broadphase(&mut grid, &proxies) {
+        if !team_collision.collides(*bodies[i].7, *bodies[j].7) {
+            continue;
+        }
+        let pos_i = bodies[i].1.translation.truncate();
+        let delta = bodies[j].1.translation.truncate() - pos_i;
+        let dist = delta.length();
+        let overlap = 2. * PLAYER_RADIUS - dist;
+        if overlap <= 0. {
+            continue;
+        }
+        let normal = if dist > 0. { delta / dist } else { Vec2::X };
+        events.0.push(SimEvent::Collision {
+            kind: CollisionKind::Player,
+            a: bodies[i].0,
+            b: bodies[j].0,
+            point: pos_i + delta / 2.,
+        });
+        let (mass_i, mass_j) = (*bodies[i].6, *bodies[j].6);
+        let (share_i, share_j) = separation_shares(mass_i, mass_j);
+        pushes[i] -= normal * overlap * share_i;
+        pushes[j] += normal * overlap * share_j;
+
+        let (vel_i, vel_j) = (bodies[i].2, bodies[j].2);
+        let (dv_i, dv_j) =
+            elastic_exchange(Vec2::new(vel_i.x, vel_i.y), Vec2::new(vel_j.x, vel_j.y), mass_i, mass_j, normal);
+        bumps[i] += dv_i;
+        bumps[j] += dv_j;
+    }
+
+    for ((_, transform, _, knockback, ..), (push, bump)) in bodies.iter_mut().zip(pushes.into_iter().zip(bumps)) {
+        transform.translation += push.extend(0.);
+        knockback.0 += bump;
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Tether {
+    pub a: usize,
+    pub b: usize,
+    pub length: f32,
+}
+
+// position corrections for a and b that bring them back to `length` apart, split by mass
+// along the line between them, so swapping the two gives the same result
+pub fn tether_correction(a: Vec2, b: Vec2, length: f32, mass_a: Mass, mass_b: Mass) -> (Vec2, Vec2) {
+    let delta = b - a;
+    let dist = delta.length();
+    if dist <= length || dist == 0. {
+        return (Vec2::ZERO, Vec2::ZERO);
+    }
+    let normal = delta / dist;
+    let excess = dist - length;
+    let (share_a, share_b) = separation_shares(mass_a, mass_b);
+    (normal * excess * share_a, -normal * excess * share_b)
+}
+
+pub fn resolve_tether(
+    mut query: Query<(&mut Transform, &mut Velocity, &Player, &Mass), With<Rollback>>,
+    tether: Res<Tether>
+) {
+    let mut bodies: Vec<_> = query.iter_mut().filter(|(_, _, p, _)| p.id == tether.a || p.id == tether.b).collect();
+    bodies.sort_by_key(|(_, _, player, _)| player.id);
+    let [a, b] = bodies.as_mut_slice() else {
+        return;
+    };
+    let (pa, pb) = (a.0.translation.truncate(), b.0.translation.truncate());
+    let (push_a, push_b) = tether_correction(pa, pb, tether.length, *a.3, *b.3);
+    if push_a == Vec2::ZERO && push_b == Vec2::ZERO {
+        return;
+    }
+    a.0.translation += push_a.extend(0.);
+    b.0.translation += push_b.extend(0.);
+
+    // the taut tether also stops either side from moving further away
+    let normal = (pb - pa).normalize();
+    for (vel, away) in [(&mut a.1, -normal), (&mut b.1, normal)] {
+        let outward = Vec2::new(vel.x, vel.y).dot(away);
+        if outward > 0. {
+            vel.x -= away.x * outward;
+            vel.y -= away.y * outward;
+        }
+    }
+}
+
+pub fn draw_tether(mut gizmos: Gizmos, query: Query<(&Transform, &Player)>, tether: Res<Tether>) {
+    let end = |id| query.iter().find(|(_, p)| p.id == id).map(|(t, _)| t.translation.truncate());
+    if let (Some(a), Some(b)) = (end(tether.a), end(tether.b)) {
+        let slack = a.distance(b) < tether.length - 1.;
+        let color = if slack { Color::srgb(0.6, 0.6, 0.6) } else { Color::srgb(1., 0.6, 0.2) };
+        gizmos.line_2d(a, b, color);
+    }
+}
+
+type WallBody = (
+    Entity,
+    &'static mut Transform,
+    &'static mut Velocity,
+    &'static PrevPosition,
+    &'static CollisionLayer,
+    &'static Player,
+);
+
+pub fn resolve_wall_collisions(
+    mut players: Query<WallBody, RollbackPlayer>,
+    walls: Query<(Entity, &Transform, &Wall, &CollisionLayer), Without<Player>>,
+    mut events: ResMut<SimEvents>
+) {
+    let mut walls: Vec<_> = walls.iter().collect();
+    walls.sort_by(|(_, a, ..), (_, b, ..)| {
+        a.translation.x.total_cmp(&b.translation.x).then(a.translation.y.total_cmp(&b.translation.y))
+    });
+    let mut players: Vec<_> = players.iter_mut().collect();
+    // events have to come out in the same order on every peer
+    players.sort_by_key(|(.., player)| player.id);
+
+    for (entity, transform, vel, prev, layer, _) in players.iter_mut() {
+        let prev_center = prev.0;
+        for (wall_entity, wall_transform, wall, wall_layer) in walls.iter() {
+            if !layer.interacts(wall_layer) {
+                continue;
+            }
+            let center = transform.translation.truncate();
+            let box_center = wall_transform.translation.truncate();
+            let Some((normal, depth)) = circle_box_contact(center, PLAYER_RADIUS, box_center, wall.half_size) else {
+                continue;
+            };
+            if !wall.blocks(normal, prev_center, box_center, PLAYER_RADIUS) {
+                continue;
+            }
+            transform.translation += (normal * depth).extend(0.);
+            events.0.push(SimEvent::Collision {
+                kind: CollisionKind::Wall,
+                a: *entity,
+                b: *wall_entity,
+                point: transform.translation.truncate() - normal * PLAYER_RADIUS,
+            });
+            let slid = slide(Vec2::new(vel.x, vel.y), normal);
+            vel.x = slid.x;
+            vel.y = slid.y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+    use crate::harness::{spawn_rollback, Harness};
+    use crate::script::InputScript;
+    use crate::PLAYER_SPEED;
+
+    #[test]
+    fn layers_interact_only_when_both_sides_filter_each_other() {
+        let (player, projectile, wall) = (CollisionLayer::player(), CollisionLayer::projectile(), CollisionLayer::wall());
+        assert!(player.interacts(&player));
+        assert!(player.interacts(&projectile) && projectile.interacts(&player));
+        assert!(!projectile.interacts(&projectile));
+        assert!(projectile.interacts(&wall));
+        assert!(!wall.interacts(&wall));
+        // a ball that only looks for players bumps into them but not into projectiles
+        let ball = CollisionLayer::new(CollisionLayer::BALL, CollisionLayer::PLAYER);
+        assert!(ball.interacts(&player));
+        assert!(!ball.interacts(&projectile));
+        assert_eq!(candidate_pairs(&[player, projectile, projectile, wall]), [(0, 1), (0, 2), (0, 3), (1, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn grid_finds_the_same_pairs_as_brute_force() {
+        let mut rng = crate::rng::SimRng::new(7);
+        let layers = [CollisionLayer::player(), CollisionLayer::projectile()];
+        let proxies: Vec<_> = (0..200)
+            .map(|i| Proxy {
+                pos: Vec2::new(rng.next_f32() * 1200. - 600., rng.next_f32() * 680. - 340.),
+                radius: 5. + rng.next_f32() * 30.,
+                layer: layers[i % 2],
+            })
+            .collect();
+        let brute = brute_force_pairs(&proxies);
+        assert!(brute.len() > 10, "too sparse to mean anything");
+        assert_eq!(broadphase(&mut SpatialGrid::default(), &proxies), brute);
+        // a cell smaller than the colliders still can't miss or repeat a pair
+        let mut tiny = SpatialGrid::new(8.);
+        tiny.rebuild(&proxies);
+        assert_eq!(tiny.pairs(&proxies), brute);
+    }
+
+    #[test]
+    fn diagonal_run_into_a_wall_slides_along_it() {
+        let mut harness = Harness::new(1, InputScript::parse("1 0 ur").unwrap());
+        let player = harness.player(0);
+        let world = harness.app.world_mut();
+        world.get_mut::<Transform>(player).unwrap().translation = Vec3::ZERO;
+        world.spawn((Transform::from_xyz(40., 0., 0.), Wall::solid(Vec2::new(10., 300.)), CollisionLayer::wall()));
+        harness.run_to(60);
+        let (_, pos, vel) = harness.players()[0];
+        // pinned against the face at x = 30, still climbing at the full vertical speed
+        assert!((pos.x - (30. - PLAYER_RADIUS)).abs() < 1e-3, "ended up at {pos}");
+        assert!((pos.y - PLAYER_SPEED).abs() < 1e-3, "ended up at {pos}");
+        assert_eq!((vel.x, vel.y), (0., PLAYER_SPEED));
+    }
+
+    #[test]
+    fn slide_keeps_the_part_along_the_surface_and_reflect_flips_the_rest() {
+        let vel = Vec2::new(200., 150.);
+        assert_eq!(slide(vel, Vec2::NEG_X), Vec2::new(0., 150.));
+        assert_eq!(reflect(vel, Vec2::NEG_X), Vec2::new(-200., 150.));
+        // moving away from the surface already
+        assert_eq!(slide(vel, Vec2::X), vel);
+        assert_eq!(reflect(vel, Vec2::X), vel);
+    }
+
+    #[test]
+    fn the_lighter_player_takes_more_of_the_push() {
+        let (heavy, light) = separation_shares(Mass(3.), Mass(1.));
+        assert!(light > heavy);
+        assert_eq!((heavy, light), (0.25, 0.75));
+        assert_eq!(heavy + light, 1.);
+        assert_eq!(separation_shares(Mass::default(), Mass::default()), (0.5, 0.5));
+        assert_eq!(separation_shares(Mass(0.), Mass(0.)), (0.5, 0.5));
+    }
+
+    #[test]
+    fn one_way_platform_lets_players_up_through_but_holds_them_from_above() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 u\n1 1 d").unwrap());
+        for (id, start) in [(0, Vec3::new(-200., -60., 0.)), (1, Vec3::new(200., 60., 0.))] {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = start;
+        }
+        let platform = Wall { half_size: Vec2::new(300., 5.), sides: SIDE_TOP };
+        harness.app.world_mut().spawn((Transform::default(), platform, CollisionLayer::wall()));
+        harness.run_to(60);
+        let players = harness.players();
+        assert!((players[0].1.y - (PLAYER_SPEED - 60.)).abs() < 1e-3, "came up to {}", players[0].1);
+        assert!((players[1].1.y - (5. + PLAYER_RADIUS)).abs() < 1e-3, "came down to {}", players[1].1);
+    }
+
+    #[test]
+    fn under_euler_a_platform_judges_the_side_from_where_the_player_really_was() {
+        // euler moves on frame 11 with frame 10's downward velocity, landing on the platform
+        // just as the input turns upward
+        let mut harness = Harness::new(1, InputScript::parse("1 0 d\n11 0 u").unwrap());
+        harness.app.insert_resource(crate::sim::Integrator::Euler);
+        let player = harness.player(0);
+        harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(0., 60., 0.);
+        let platform = Wall { half_size: Vec2::new(300., 5.), sides: SIDE_TOP };
+        harness.app.world_mut().spawn((Transform::default(), platform, CollisionLayer::wall()));
+        harness.run_to(10);
+        assert!(harness.players()[0].1.y > 5. + PLAYER_RADIUS);
+        harness.run_to(11);
+        let (_, pos, vel) = harness.players()[0];
+        assert!((pos.y - (5. + PLAYER_RADIUS)).abs() < 1e-3, "held at {pos}");
+        assert_eq!(vel.y, PLAYER_SPEED);
+    }
+
+    #[test]
+    fn players_pulling_apart_stay_exactly_a_tether_length_apart() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 l\n1 1 r").unwrap());
+        harness.app.insert_resource(Tether { a: 0, b: 1, length: 100. });
+        for (id, x) in [(0, -40.), (1, 40.)] {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(x, 0., 0.);
+        }
+        harness.run_to(60);
+        let players = harness.players();
+        let (a, b) = (players[0].1, players[1].1);
+        assert!((a.distance(b) - 100.).abs() < 1e-3, "{a} {b}");
+        // equal masses, so both sides gave the same
+        assert!((a.x + b.x).abs() < 1e-3, "{a} {b}");
+        assert_eq!(players[1].2.x, 0.);
+
+        let (push_a, push_b) = tether_correction(Vec2::ZERO, Vec2::new(130., 0.), 100., Mass(1.), Mass(2.));
+        assert_eq!((push_a, push_b), (Vec2::new(20., 0.), Vec2::new(-10., 0.)));
+    }
+
+    // (id, x, velocity x, mass) in spawn order, gives back each player's knockback by id
+    fn bump(bodies: [(usize, f32, f32, f32); 2]) -> [Vec2; 2] {
+        let mut world = World::new();
+        world.init_resource::<SimEvents>();
+        world.insert_resource(TeamCollision::On);
+        let entities = bodies.map(|(id, x, vel, mass)| {
+            let bundle = (
+                Transform::from_xyz(x, 0., 0.),
+                Velocity { x: vel, y: 0. },
+                Knockback::default(),
+                Player { id },
+                CollisionLayer::player(),
+                Mass(mass),
+                Team(id),
+            );
+            (id, spawn_rollback(&mut world, bundle))
+        });
+        world.run_system_once(resolve_player_collisions);
+        let mut knockbacks = entities.map(|(id, e)| (id, world.get::<Knockback>(e).unwrap().0));
+        knockbacks.sort_by_key(|(id, _)| *id);
+        knockbacks.map(|(_, k)| k)
+    }
+
+    #[test]
+    fn striking_a_standing_player_conserves_momentum() {
+        let (va, ma, mb) = (300., 1., 2.);
+        let [ka, kb] = bump([(0, 0., va, ma), (1, PLAYER_RADIUS, 0., mb)]);
+        let before = ma * va;
+        let after = ma * (va + ka.x) + mb * kb.x;
+        assert!((after - before).abs() < 1e-3, "{before} became {after}");
+        // elastic, so no energy is lost either
+        let energy = ma * (va + ka.x).powi(2) + mb * kb.x.powi(2);
+        assert!((energy - ma * va * va).abs() < 1e-1, "{energy}");
+        assert_eq!((ka.y, kb.y), (0., 0.));
+        // independent of which one the query hands out first
+        assert_eq!(bump([(1, PLAYER_RADIUS, 0., mb), (0, 0., va, ma)]), [ka, kb]);
+
+        // equal masses just swap velocities
+        let (dv_a, dv_b) = elastic_exchange(Vec2::new(va, 0.), Vec2::ZERO, Mass(1.), Mass(1.), Vec2::X);
+        assert_eq!((dv_a, dv_b), (Vec2::new(-va, 0.), Vec2::new(va, 0.)));
+    }
+
+    #[test]
+    fn overlapping_players_make_one_event_per_frame() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 r\n1 1 l").unwrap());
+        let pair = [harness.player(0), harness.player(1)];
+        for (player, x) in pair.into_iter().zip([-20., 20.]) {
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(x, 0., 0.);
+        }
+        let mut expected = pair;
+        expected.sort();
+        let mut touching = 0;
+        // the bump sends them apart, then they run back into each other
+        for frame in 1..=30 {
+            harness.run_to(frame);
+            let events = &harness.app.world().resource::<SimEvents>().0;
+            let hits: Vec<_> = events
+                .iter()
+                .filter_map(|e| match e {
+                    SimEvent::Collision { kind: CollisionKind::Player, a, b, point } => Some(([*a, *b], *point)),
+                    _ => None,
+                })
+                .collect();
+            match hits[..] {
+                [] => assert!(frame > 1, "they start out overlapping"),
+                [(mut entities, point)] => {
+                    entities.sort();
+                    assert_eq!(entities, expected, "frame {frame}");
+                    assert!(point.x.abs() < 1e-3, "they meet in the middle: {point}");
+                    touching += 1;
+                }
+                _ => panic!("frame {frame}: {events:?}"),
+            }
+        }
+        assert!(touching > 1, "{touching}");
+    }
+
+    #[test]
+    fn with_team_collision_off_only_enemies_push_each_other_apart() {
+        let separations = |setting| {
+            let mut world = World::new();
+            world.init_resource::<SimEvents>();
+            world.insert_resource(setting);
+            // teammates 0 and 2 overlapping, and enemies 1 and 4 further up
+            let players = [(0, 0., 0.), (2, 20., 0.), (1, 0., 300.), (4, 20., 300.)].map(|(id, x, y)| {
+                let bundle = (
+                    Transform::from_xyz(x, y, 0.),
+                    Velocity { x: 0., y: 0. },
+                    Knockback::default(),
+                    Player { id },
+                    CollisionLayer::player(),
+                    Mass(1.),
+                    Team::of_player(id),
+                );
+                spawn_rollback(&mut world, bundle)
+            });
+            world.run_system_once(resolve_player_collisions);
+            let x = |e| world.get::<Transform>(e).unwrap().translation.x;
+            let events = world.resource::<SimEvents>().0.len();
+            (x(players[1]) - x(players[0]), x(players[3]) - x(players[2]), events)
+        };
+        let (teammates, enemies, events) = separations(TeamCollision::Off);
+        assert_eq!(teammates, 20.);
+        assert!((enemies - 2. * PLAYER_RADIUS).abs() < 1e-3, "{enemies}");
+        assert_eq!(events, 1);
+
+        let (teammates, enemies, events) = separations(TeamCollision::On);
+        assert!((teammates - 2. * PLAYER_RADIUS).abs() < 1e-3, "{teammates}");
+        assert!((enemies - 2. * PLAYER_RADIUS).abs() < 1e-3, "{enemies}");
+        assert_eq!(events, 2);
+        assert!(!TeamCollision::Off.collides(Team(1), Team(1)) && TeamCollision::Off.collides(Team(0), Team(1)));
+    }
+}
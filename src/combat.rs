@@ -0,0 +1,247 @@
+use std::f32::consts::TAU;
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use crate::sim::FrameCounter;
+use crate::{Player, PLAYER_RADIUS};
+
+pub const PLAYER_MAX_HP: i32 = 100;
+pub const STUN_FRAMES: u32 = 30;
+// frames after a hit in which the next one keeps the combo going
+pub const COMBO_WINDOW: u32 = 90;
+pub const MAX_COMBO: u32 = 5;
+pub const SHIELD_MAX_HP: i32 = 30;
+// frames without taking damage before the shield starts coming back
+pub const SHIELD_REGEN_DELAY: u32 = 120;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Health {
+    pub hp: i32,
+    pub max: i32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health::new(PLAYER_MAX_HP)
+    }
+}
+
+impl Health {
+    pub fn new(max: i32) -> Self {
+        Self { hp: max, max }
+    }
+
+    pub fn damage(&mut self, amount: i32) {
+        self.hp = (self.hp - amount).max(0);
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.hp = (self.hp + amount).min(self.max);
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max <= 0 {
+            return 0.;
+        }
+        self.hp as f32 / self.max as f32
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Stun {
+    pub frames: u32,
+}
+
+impl Stun {
+    // a new hit refreshes the stun instead of stacking it
+    pub fn apply(&mut self, frames: u32) {
+        self.frames = self.frames.max(frames);
+    }
+
+    // true while the player has to ignore input this frame
+    pub fn tick(&mut self) -> bool {
+        let stunned = self.frames > 0;
+        self.frames = self.frames.saturating_sub(1);
+        stunned
+    }
+}
+
+// Soaks up damage before Health does, and refills one point a frame once the
+// player went `SHIELD_REGEN_DELAY` frames without getting hit.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Shield {
+    pub hp: i32,
+    pub last_hit: u32,
+}
+
+impl Default for Shield {
+    fn default() -> Self {
+        Self { hp: SHIELD_MAX_HP, last_hit: 0 }
+    }
+}
+
+impl Shield {
+    // takes what it can of `amount`, the rest goes through to health
+    pub fn absorb(&mut self, amount: i32, frame: u32) -> i32 {
+        self.last_hit = frame;
+        let absorbed = amount.min(self.hp);
+        self.hp -= absorbed;
+        amount - absorbed
+    }
+
+    pub fn regen(&mut self, frame: u32) {
+        if frame.saturating_sub(self.last_hit) >= SHIELD_REGEN_DELAY {
+            self.hp = (self.hp + 1).min(SHIELD_MAX_HP);
+        }
+    }
+}
+
+pub fn damage(health: &mut Health, shield: &mut Shield, amount: i32, frame: u32) {
+    health.damage(shield.absorb(amount, frame));
+}
+
+pub fn regen_shields(mut query: Query<&mut Shield, With<Rollback>>, frame: Res<FrameCounter>) {
+    for mut shield in query.iter_mut() {
+        shield.regen(frame.0);
+    }
+}
+
+pub fn draw_shields(mut gizmos: Gizmos, query: Query<(&Transform, &Shield), With<Player>>) {
+    for (transform, shield) in query.iter() {
+        if shield.hp > 0 {
+            let alpha = 0.8 * shield.hp as f32 / SHIELD_MAX_HP as f32;
+            gizmos.circle_2d(transform.translation.truncate(), PLAYER_RADIUS + 5., Color::srgba(0.3, 0.8, 1., alpha));
+        }
+    }
+}
+
+// Points for landing hits, each hit within `COMBO_WINDOW` of the last one is worth one more,
+// up to `MAX_COMBO`. A lapsed combo needs no reset, it just gets ignored.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Combo {
+    pub multiplier: u32,
+    pub last_hit: u32,
+    pub points: u32,
+}
+
+impl Combo {
+    pub fn multiplier_at(&self, frame: u32) -> u32 {
+        if self.multiplier == 0 || frame.saturating_sub(self.last_hit) > COMBO_WINDOW {
+            1
+        } else {
+            self.multiplier
+        }
+    }
+
+    pub fn hit(&mut self, frame: u32) {
+        self.multiplier = if self.multiplier > 0 && frame.saturating_sub(self.last_hit) <= COMBO_WINDOW {
+            (self.multiplier + 1).min(MAX_COMBO)
+        } else {
+            1
+        };
+        self.last_hit = frame;
+        self.points += self.multiplier;
+    }
+}
+
+// green at full health, red at zero
+pub fn health_color(fraction: f32) -> Color {
+    let f = fraction.clamp(0., 1.);
+    Color::srgb(1. - f, f, 0.)
+}
+
+#[derive(Resource)]
+pub struct HealthTint;
+
+// render-only: the material is never part of the rollback state
+pub fn tint_by_health(
+    query: Query<(&Health, &Handle<ColorMaterial>), With<Player>>,
+    mut materials: ResMut<Assets<ColorMaterial>>
+) {
+    for (health, handle) in query.iter() {
+        let color = health_color(health.fraction());
+        if materials.get(handle).is_some_and(|m| m.color != color) {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+    }
+}
+
+pub fn draw_stun_stars(mut gizmos: Gizmos, query: Query<(&Transform, &Stun), With<Player>>, time: Res<Time>) {
+    let spin = time.elapsed_seconds() * 4.;
+    for (transform, stun) in query.iter() {
+        if stun.frames == 0 {
+            continue;
+        }
+        let head = transform.translation.truncate() + Vec2::Y * PLAYER_RADIUS;
+        for i in 0..3 {
+            let angle = spin + i as f32 * TAU / 3.;
+            let star = head + Vec2::new(angle.cos() * 15., angle.sin() * 5.);
+            gizmos.circle_2d(star, 3., Color::srgb(1., 0.9, 0.2));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_health_is_green_and_none_is_red() {
+        let mut health = Health::new(100);
+        assert_eq!(health_color(health.fraction()), Color::srgb(0., 1., 0.));
+        health.damage(150);
+        assert_eq!(health_color(health.fraction()), Color::srgb(1., 0., 0.));
+        assert_eq!(health_color(0.5), Color::srgb(0.5, 0.5, 0.));
+        assert_eq!(health_color(2.), health_color(1.));
+    }
+
+    #[test]
+    fn stun_counts_down_and_a_new_hit_refreshes_it() {
+        let mut stun = Stun::default();
+        stun.apply(2);
+        stun.apply(1);
+        assert_eq!([stun.tick(), stun.tick(), stun.tick()], [true, true, false]);
+        stun.apply(3);
+        stun.tick();
+        stun.apply(3);
+        assert_eq!(stun.frames, 3);
+    }
+
+    #[test]
+    fn quick_hits_raise_the_multiplier_and_a_pause_resets_it() {
+        let mut combo = Combo::default();
+        assert_eq!(combo.multiplier_at(0), 1);
+        combo.hit(100);
+        combo.hit(100 + COMBO_WINDOW);
+        combo.hit(100 + 2 * COMBO_WINDOW);
+        assert_eq!((combo.multiplier, combo.points), (3, 1 + 2 + 3));
+        assert_eq!(combo.multiplier_at(100 + 3 * COMBO_WINDOW), 3);
+
+        // lapsed, so the next hit starts over
+        let late = 100 + 3 * COMBO_WINDOW + 1;
+        assert_eq!(combo.multiplier_at(late), 1);
+        combo.hit(late);
+        assert_eq!((combo.multiplier, combo.points), (1, 7));
+
+        (0..2 * MAX_COMBO).for_each(|i| combo.hit(late + i));
+        assert_eq!(combo.multiplier, MAX_COMBO);
+    }
+
+    #[test]
+    fn shield_takes_damage_first_and_regenerates_after_the_delay() {
+        let (mut health, mut shield) = (Health::new(100), Shield::default());
+        damage(&mut health, &mut shield, 20, 10);
+        assert_eq!((shield.hp, health.hp), (SHIELD_MAX_HP - 20, 100));
+        // only what the shield couldn't hold gets through
+        damage(&mut health, &mut shield, 25, 11);
+        assert_eq!((shield.hp, health.hp), (0, 100 - (25 - (SHIELD_MAX_HP - 20))));
+
+        (12..11 + SHIELD_REGEN_DELAY).for_each(|frame| shield.regen(frame));
+        assert_eq!(shield.hp, 0);
+        shield.regen(11 + SHIELD_REGEN_DELAY);
+        assert_eq!(shield.hp, 1);
+        (0..2 * SHIELD_MAX_HP as u32).for_each(|i| shield.regen(12 + SHIELD_REGEN_DELAY + i));
+        assert_eq!(shield.hp, SHIELD_MAX_HP);
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use bevy::prelude::*;
+use bevy_ggrs::{PlayerInputs, Session};
+use crate::combat::Health;
+use crate::sim::{FrameCounter, FrameHash, SimEvent, SimEvents};
+use crate::teams::Score;
+use crate::{Config, Player, Velocity};
+
+const SLOWMO_KEY: KeyCode = KeyCode::F1;
+const CHECKSUM_KEY: KeyCode = KeyCode::F2;
+const INPUT_HISTORY_KEY: KeyCode = KeyCode::F3;
+
+#[derive(Resource)]
+pub struct SlowMotion {
+    pub factor: u32,
+    pub enabled: bool,
+    frame: u32,
+}
+
+impl SlowMotion {
+    pub fn new(factor: u32) -> Self {
+        Self {
+            factor: factor.max(1),
+            enabled: false,
+            frame: 0,
+        }
+    }
+
+    // true if the rollback schedule may advance on this render frame
+    pub fn step(&mut self) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let run = self.frame.is_multiple_of(self.factor);
+        self.frame = self.frame.wrapping_add(1);
+        run
+    }
+}
+
+pub fn toggle_slow_motion(
+    input: Res<ButtonInput<KeyCode>>,
+    session: Res<Session<Config>>,
+    mut slowmo: ResMut<SlowMotion>
+) {
+    if !input.just_pressed(SLOWMO_KEY) {
+        return;
+    }
+    // throttling the schedule locally would make us fall behind remote peers
+    if !matches!(*session, Session::SyncTest(_)) {
+        warn!("slow motion is only available in SyncTest sessions");
+        return;
+    }
+    slowmo.enabled = !slowmo.enabled;
+    slowmo.frame = 0;
+    info!("slow motion {} (1/{})", if slowmo.enabled { "on" } else { "off" }, slowmo.factor);
+}
+
+// GgrsSchedule is driven by virtual time, so pausing it on skipped frames slows the simulation down
+pub fn drive_slow_motion(mut slowmo: ResMut<SlowMotion>, mut time: ResMut<Time<Virtual>>) {
+    if slowmo.step() {
+        time.unpause();
+    } else {
+        time.pause();
+    }
+}
+
+// players must be sorted by id, floats are hashed by their bits
+pub fn state_checksum(frame: u32, score: &Score, players: &[(usize, Vec2, Velocity, Health)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    frame.hash(&mut hasher);
+    score.hash(&mut hasher);
+    for (id, pos, vel, health) in players {
+        id.hash(&mut hasher);
+        [pos.x, pos.y, vel.x, vel.y].map(f32::to_bits).hash(&mut hasher);
+        health.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// logs on demand instead of waiting for the next SyncTest comparison
+pub fn log_checksum(
+    input: Res<ButtonInput<KeyCode>>,
+    session: Res<Session<Config>>,
+    frame: Res<FrameCounter>,
+    frame_hash: Res<FrameHash>,
+    score: Res<Score>,
+    query: Query<(&Transform, &Velocity, &Health, &Player)>
+) {
+    if !input.just_pressed(CHECKSUM_KEY) {
+        return;
+    }
+    if !matches!(*session, Session::SyncTest(_)) {
+        warn!("manual checksums are only available in SyncTest sessions");
+        return;
+    }
+    let mut players: Vec<_> = query
+        .iter()
+        .map(|(t, vel, health, player)| (player.id, t.translation.truncate(), *vel, *health))
+        .collect();
+    players.sort_by_key(|(id, ..)| *id);
+    info!(
+        "frame {} checksum {:016x} frame hash {:016x}",
+        frame.0,
+        state_checksum(frame.0, &score, &players),
+        frame_hash.0
+    );
+}
+
+#[derive(Resource)]
+pub struct VelocityGizmos {
+    pub scale: f32,
+}
+
+pub fn velocity_gizmo_line(pos: Vec2, vel: &Velocity, scale: f32) -> (Vec2, Vec2) {
+    (pos, pos + Vec2::new(vel.x, vel.y) * scale)
+}
+
+// render-only, reads the rollback velocity but never writes it
+pub fn draw_velocity_gizmos(
+    mut gizmos: Gizmos,
+    query: Query<(&Transform, &Velocity), With<Player>>,
+    config: Res<VelocityGizmos>
+) {
+    for (transform, vel) in query.iter() {
+        let (start, end) = velocity_gizmo_line(transform.translation.truncate(), vel, config.scale);
+        gizmos.line_2d(start, end, Color::srgb(1., 1., 0.));
+    }
+}
+
+#[derive(Resource)]
+pub struct MaxFrames {
+    pub frames: u32,
+    pub dump_state: bool,
+    // what the dump has to come out as, with the file it came from
+    pub expect: Option<(PathBuf, String)>,
+}
+
+pub fn should_exit(frame: u32, max_frames: u32) -> bool {
+    frame >= max_frames
+}
+
+// `players` is (id, position, velocity) sorted by id
+pub fn state_dump(frame: u32, players: &[(usize, Vec2, Velocity)]) -> String {
+    let mut dump = format!("frame {frame}\n");
+    for (id, pos, vel) in players {
+        dump += &format!("player {id} pos {} {} vel {} {}\n", pos.x, pos.y, vel.x, vel.y);
+    }
+    dump
+}
+
+pub fn exit_after_max_frames(
+    frame: Res<FrameCounter>,
+    max: Res<MaxFrames>,
+    players: Query<(&Transform, &Velocity, &Player)>,
+    mut exit: EventWriter<AppExit>,
+    mut done: Local<bool>
+) {
+    if *done || !should_exit(frame.0, max.frames) {
+        return;
+    }
+    *done = true;
+
+    let mut players: Vec<_> = players.iter().map(|(t, vel, p)| (p.id, t.translation.truncate(), *vel)).collect();
+    players.sort_by_key(|(id, ..)| *id);
+    let dump = state_dump(frame.0, &players);
+    if max.dump_state {
+        print!("{dump}");
+    }
+    if let Some((path, expected)) = &max.expect {
+        let mismatch = dump.lines().zip(expected.lines()).find(|(got, want)| got.trim() != want.trim());
+        if mismatch.is_some() || dump.lines().count() != expected.lines().count() {
+            let (got, want) = mismatch.unwrap_or(("<line count>", "<line count>"));
+            error!("state differs from {}: got `{got}`, expected `{want}`", path.display());
+            exit.send(AppExit::error());
+            return;
+        }
+    }
+    exit.send(AppExit::Success);
+}
+
+// counts render frames against simulated frames over each interval
+#[derive(Resource)]
+pub struct FrameReport {
+    timer: Timer,
+    renders: u32,
+    sim_start: u32,
+}
+
+impl FrameReport {
+    pub fn new(interval_secs: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(interval_secs, TimerMode::Repeating),
+            renders: 0,
+            sim_start: 0,
+        }
+    }
+}
+
+// render frames per simulated frame; 1 is in lockstep, below 1 rendering is falling behind
+pub fn render_sim_ratio(renders: u32, sim_frames: u32) -> Option<f32> {
+    (sim_frames > 0).then(|| renders as f32 / sim_frames as f32)
+}
+
+pub fn report_frame_ratio(time: Res<Time<Real>>, frame: Res<FrameCounter>, mut report: ResMut<FrameReport>) {
+    report.renders += 1;
+    if !report.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let sim_frames = frame.0.wrapping_sub(report.sim_start);
+    let renders = report.renders;
+    report.renders = 0;
+    report.sim_start = frame.0;
+
+    match render_sim_ratio(renders, sim_frames) {
+        None => info!("{renders} renders, simulation stalled"),
+        Some(ratio) if ratio < 0.9 => {
+            warn!("rendering can't keep up: {sim_frames} sim frames over {renders} renders ({ratio:.2})")
+        }
+        Some(ratio) if ratio > 1.1 => {
+            info!("simulation behind rendering: {sim_frames} sim frames over {renders} renders ({ratio:.2})")
+        }
+        Some(ratio) => info!("{renders} renders for {sim_frames} sim frames ({ratio:.2})"),
+    }
+}
+
+// last K packed inputs per player, for diagnostics only; the simulation never reads it
+#[derive(Resource)]
+pub struct InputHistory {
+    capacity: usize,
+    players: Vec<VecDeque<(u32, u8)>>,
+}
+
+impl InputHistory {
+    pub fn new(players: usize, capacity: usize) -> Self {
+        Self {
+            capacity,
+            players: vec![VecDeque::with_capacity(capacity + 1); players],
+        }
+    }
+
+    // re-simulated frames replace what was recorded for them
+    pub fn record(&mut self, frame: u32, player: usize, wasd: u8) {
+        let history = &mut self.players[player];
+        while history.back().is_some_and(|&(recorded, _)| recorded >= frame) {
+            history.pop_back();
+        }
+        history.push_back((frame, wasd));
+        while history.len() > self.capacity {
+            history.pop_front();
+        }
+    }
+
+    pub fn inputs(&self, player: usize) -> impl Iterator<Item = (u32, u8)> + '_ {
+        self.players[player].iter().copied()
+    }
+}
+
+// PlayerInputs only exists while the rollback schedule runs, so that's where it gets copied out
+pub fn record_input_history(
+    mut history: ResMut<InputHistory>,
+    inputs: Res<PlayerInputs<Config>>,
+    frame: Res<FrameCounter>
+) {
+    for (player, (input, _)) in inputs.iter().enumerate() {
+        history.record(frame.0, player, input.wasd);
+    }
+}
+
+pub fn dump_input_history(input: Res<ButtonInput<KeyCode>>, history: Res<InputHistory>) {
+    if !input.just_pressed(INPUT_HISTORY_KEY) {
+        return;
+    }
+    for player in 0..history.players.len() {
+        let line: Vec<String> = history
+            .inputs(player)
+            .map(|(frame, wasd)| format!("{frame}:{wasd:02x}"))
+            .collect();
+        info!("player {player} inputs: {}", line.join(" "));
+    }
+}
+
+#[derive(Resource)]
+pub struct LogEvents;
+
+// each simulated frame's events once, the queue stays put until the next frame runs
+pub fn log_sim_events(events: Res<SimEvents>, frame: Res<FrameCounter>, mut logged: Local<Option<u32>>) {
+    if *logged == Some(frame.0) {
+        return;
+    }
+    *logged = Some(frame.0);
+    for event in &events.0 {
+        match event {
+            SimEvent::Collision { kind, a, b, point } => {
+                info!("frame {}: {kind:?} collision of {a} and {b} at {point}", frame.0)
+            }
+            SimEvent::Relocated { player, to } => info!("frame {}: player {player} relocated to {to}", frame.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::log::tracing_subscriber;
+    use bevy::utils::tracing;
+    use super::*;
+    use crate::harness::Harness;
+    use crate::script::InputScript;
+
+    #[test]
+    fn slow_motion_steps_once_every_factor_frames() {
+        let mut slowmo = SlowMotion::new(3);
+        assert!((0..5).all(|_| slowmo.step()), "runs every frame while off");
+        slowmo.enabled = true;
+        let steps: Vec<_> = (0..7).map(|_| slowmo.step()).collect();
+        assert_eq!(steps, [true, false, false, true, false, false, true]);
+        assert_eq!(SlowMotion::new(0).factor, 1);
+    }
+
+    #[test]
+    fn velocity_gizmo_starts_at_the_player_and_scales_the_velocity() {
+        let (start, end) = velocity_gizmo_line(Vec2::new(10., -5.), &Velocity { x: 200., y: -100. }, 0.25);
+        assert_eq!(start, Vec2::new(10., -5.));
+        assert_eq!(end, Vec2::new(60., -30.));
+        let (start, end) = velocity_gizmo_line(Vec2::ONE, &Velocity { x: 0., y: 0. }, 0.25);
+        assert_eq!(start, end);
+    }
+
+    fn run_until_exit(max: MaxFrames) -> (u32, AppExit) {
+        let mut harness = crate::harness::Harness::new(2, Default::default());
+        harness.app.insert_resource(max);
+        loop {
+            harness.app.update();
+            if let Some(exit) = harness.app.should_exit() {
+                return (harness.frame(), exit);
+            }
+            assert!(harness.frame() < 100, "never exited");
+        }
+    }
+
+    #[test]
+    fn max_frames_exits_on_exactly_that_frame() {
+        let (frame, exit) = run_until_exit(MaxFrames { frames: 10, dump_state: false, expect: None });
+        assert_eq!(frame, 10);
+        assert_eq!(exit, AppExit::Success);
+    }
+
+    #[test]
+    fn max_frames_fails_on_an_unexpected_state() {
+        let expect = Some(("expected.txt".into(), "frame 10\nplayer 0 pos 1 2 vel 0 0\n".to_string()));
+        let (_, exit) = run_until_exit(MaxFrames { frames: 10, dump_state: false, expect });
+        assert_eq!(exit, AppExit::error());
+        assert_eq!(
+            state_dump(3, &[(0, Vec2::new(1., 2.), Velocity { x: 0., y: -4. })]),
+            "frame 3\nplayer 0 pos 1 2 vel 0 -4\n"
+        );
+    }
+
+    #[test]
+    fn input_history_keeps_the_last_k_in_order() {
+        let mut history = InputHistory::new(2, 3);
+        for frame in 1..=5 {
+            history.record(frame, 0, frame as u8);
+        }
+        assert_eq!(history.inputs(0).collect::<Vec<_>>(), [(3, 3), (4, 4), (5, 5)]);
+        assert_eq!(history.inputs(1).count(), 0);
+        // a rollback to frame 4 replaces what was recorded from there on
+        history.record(4, 0, 9);
+        assert_eq!(history.inputs(0).collect::<Vec<_>>(), [(3, 3), (4, 9)]);
+    }
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn checksum_key_logs_the_current_frame() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 r").unwrap());
+        harness.run_to(12);
+        harness.app.init_resource::<ButtonInput<KeyCode>>();
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt().with_ansi(false).with_writer(move || writer.clone()).finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let world = harness.app.world_mut();
+            world.run_system_once(log_checksum);
+            assert!(captured.0.lock().unwrap().is_empty(), "logged without the key");
+            world.resource_mut::<ButtonInput<KeyCode>>().press(CHECKSUM_KEY);
+            world.run_system_once(log_checksum);
+        });
+        let logged = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(logged.lines().count(), 1, "{logged}");
+        assert!(logged.contains("frame 12 checksum "), "{logged}");
+    }
+
+    #[test]
+    fn render_sim_ratio_from_sampled_counts() {
+        assert_eq!(render_sim_ratio(60, 60), Some(1.));
+        assert_eq!(render_sim_ratio(144, 60), Some(2.4));
+        // two sim steps per render, the display is falling behind
+        assert_eq!(render_sim_ratio(30, 60), Some(0.5));
+        assert_eq!(render_sim_ratio(45, 0), None);
+        assert_eq!(render_sim_ratio(0, 0), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::HashMap;
+use bevy::ecs::system::EntityCommand;
+use bevy_ggrs::{AddRollbackCommand, GgrsPlugin, LocalInputs, LocalPlayers, ReadInputs, Session};
+use bevy_ggrs::prelude::SessionBuilder;
+use clap::Parser;
+use crate::input::InputPacked;
+use crate::script::{read_scripted_inputs, InputScript};
+use crate::sim::{Countdown, FrameCounter};
+use crate::{arena, map, net, projectile, replay, spawn_player, Config, Opt, Player, Velocity, UPS};
+
+// Runs the simulation headless in a synctest session, fed by an `InputScript` instead of
+// the keyboard, for tests that want to check where a sequence of inputs ends up. The
+// options are the command line defaults, anything else a test needs goes in `app`.
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    pub fn new(players: usize, script: InputScript) -> Self {
+        Self::with_args(players, script, &[])
+    }
+
+    // plays `replay` back the way `--play` does, with nothing scripted on top; the replay is
+    // handed over directly, so the path given to `--play` is never read
+    pub fn playback(replay: replay::Replay) -> Self {
+        let mut harness = Self::with_args(replay.players, InputScript::default(), &["--play", "-"]);
+        harness
+            .app
+            .insert_resource(replay::Playback::new(replay))
+            .add_systems(ReadInputs, replay::read_playback_inputs.after(read_scripted_inputs));
+        harness
+    }
+
+    // `extra` options go on top of the defaults, for the ones the session is built from
+    fn with_args(players: usize, script: InputScript, extra: &[&str]) -> Self {
+        let mut args = vec!["ptpOnBevy", "--synctest"];
+        args.extend(extra);
+        args.push("-p");
+        args.extend(std::iter::repeat_n("localhost", players));
+        let opt = Opt::parse_from(args);
+        let prediction = net::prediction_settings(opt.no_prediction, opt.input_delay, opt.expected_rtt);
+        let session = SessionBuilder::<Config>::new()
+            .with_num_players(players)
+            .with_check_distance(prediction.check_distance(opt.check_distance()).unwrap())
+            .start_synctest_session()
+            .unwrap();
+        let half_size = Vec2::new(opt.arena_width, opt.arena_height) / 2.;
+        let spawns = arena::spawn_positions(opt.spawn, opt.seed, players, half_size);
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GgrsPlugin::<Config>::default()));
+        add_harness_resources(&mut app, &opt, script);
+        crate::add_simulation(&mut app)
+            .insert_resource(Session::SyncTest(session))
+            .insert_resource(arena::Arena { half_size })
+            // a hair over one frame per update, so the fixed rate never falls behind
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.001 / UPS as f64)))
+            .add_systems(ReadInputs, (read_idle_inputs, read_scripted_inputs).chain());
+        // spawned right away instead of at startup, so tests can tweak them before frame 1
+        let mut commands = app.world_mut().commands();
+        for (i, pos) in spawns.into_iter().enumerate() {
+            spawn_player(&mut commands, i, pos);
+        }
+        app.world_mut().flush();
+        Harness { app }
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.app.world().resource::<FrameCounter>().0
+    }
+
+    // steps until `frame` is the last simulated frame
+    pub fn run_to(&mut self, frame: u32) {
+        while self.frame() < frame {
+            self.app.update();
+        }
+        assert_eq!(self.frame(), frame, "stepped past frame {frame}");
+    }
+
+    pub fn player(&mut self, id: usize) -> Entity {
+        let mut query = self.app.world_mut().query::<(Entity, &Player)>();
+        query.iter(self.app.world()).find(|(_, p)| p.id == id).map(|(e, _)| e).unwrap()
+    }
+
+    // (id, position, velocity) sorted by id, as the state dump takes them
+    pub fn players(&mut self) -> Vec<(usize, Vec2, Velocity)> {
+        let mut query = self.app.world_mut().query::<(&Transform, &Velocity, &Player)>();
+        let mut players: Vec<_> =
+            query.iter(self.app.world()).map(|(t, vel, p)| (p.id, t.translation.truncate(), *vel)).collect();
+        players.sort_by_key(|(id, ..)| *id);
+        players
+    }
+}
+
+fn add_harness_resources(app: &mut App, opt: &Opt, script: InputScript) {
+    app.insert_resource(Countdown {
+        frames: opt.countdown * UPS as u32,
+    })
+    .insert_resource(opt.integrator)
+    .insert_resource(opt.weapon)
+    .insert_resource(opt.team_collision)
+    .insert_resource(arena::Portals::default())
+    .insert_resource(map::MapSpec::default())
+    .insert_resource(projectile::ProjectileConfig {
+        speed: opt.projectile_speed,
+        radius: opt.projectile_radius,
+        bounces: 0,
+    })
+    .insert_resource(script);
+}
+
+// spawns `bundle` the way `add_rollback` does, for single systems run in a bare world
+pub fn spawn_rollback(world: &mut World, bundle: impl Bundle) -> Entity {
+    let id = world.spawn(bundle).id();
+    AddRollbackCommand.apply(id, world);
+    id
+}
+
+// nobody presses anything unless the script says so
+fn read_idle_inputs(mut commands: Commands, local_players: Res<LocalPlayers>) {
+    let inputs: HashMap<_, _> = local_players.0.iter().map(|&id| (id, InputPacked::new(0))).collect();
+    commands.insert_resource(LocalInputs::<Config>(inputs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PLAYER_SPEED, SPU};
+
+    #[test]
+    fn script_drives_a_player_to_where_it_should_be() {
+        // right on frames 1 through 30, then standing still
+        let script = InputScript::parse("1 0 r\n31 0 -\n").unwrap();
+        let mut harness = Harness::new(2, script);
+        harness.run_to(1);
+        let mut spawns = harness.app.world_mut().query::<(&arena::SpawnPoint, &Player)>();
+        let mut spawns: Vec<_> = spawns.iter(harness.app.world()).map(|(s, p)| (p.id, s.0)).collect();
+        spawns.sort_by_key(|(id, _)| *id);
+        // script frames are the frame counter's, so the first step already happens on frame 1
+        assert_eq!(harness.players()[0].2.x, PLAYER_SPEED);
+
+        harness.run_to(60);
+
+        let players = harness.players();
+        let (_, pos, vel) = players[0];
+        let expected = spawns[0].1 + Vec2::new(30. * PLAYER_SPEED * SPU, 0.);
+        assert!(pos.distance(expected) < 1e-3, "ended up at {pos}, expected {expected}");
+        assert_eq!((vel.x, vel.y), (0., 0.));
+        // the other handle isn't scripted and stays put
+        assert_eq!(players[1].1, spawns[1].1);
+    }
+}
@@ -0,0 +1,370 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy_ggrs::Rollback;
+use crate::abilities::SlowField;
+use crate::arena::{safest_spawn, Arena, SpawnPoint, Wrap};
+use crate::collision::Knockback;
+use crate::combat::{Health, Shield};
+use crate::map::MapSpec;
+use crate::modes::KnockedOut;
+use crate::rng::SimRng;
+use crate::sim::{SimEvent, SimEvents};
+use crate::teams::Team;
+use crate::{Player, RollbackPlayer, Velocity, SPU};
+
+// keeps the pull finite when a player sits on top of the well
+const GRAVITY_MIN_DISTANCE: f32 = 50.;
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct GravityWell {
+    pub center: Vec2,
+    pub strength: f32,
+}
+
+// inverse-distance pull toward the well's center
+pub fn gravity_acceleration(pos: Vec2, well: &GravityWell) -> Vec2 {
+    let delta = well.center - pos;
+    let dist = delta.length();
+    if dist == 0. {
+        return Vec2::ZERO;
+    }
+    delta / dist * well.strength / dist.max(GRAVITY_MIN_DISTANCE)
+}
+
+// Velocity a player has built up falling toward the well. Input velocity is recomputed
+// every frame, so the pull is kept here and added on top. It fades a little each frame,
+// like `Knockback`, which keeps it bounded so a player can still walk out of the well.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Pull(pub Vec2);
+
+const PULL_DECAY: f32 = 0.98;
+
+pub fn apply_gravity(
+    mut query: Query<(&Transform, &mut Pull, &mut Velocity), RollbackPlayer>,
+    well: Res<GravityWell>
+) {
+    for (transform, mut pull, mut vel) in query.iter_mut() {
+        let accel = gravity_acceleration(transform.translation.truncate(), &well);
+        pull.0 = pull.0 * PULL_DECAY + accel * SPU;
+        vel.x += pull.0.x;
+        vel.y += pull.0.y;
+    }
+}
+
+pub fn draw_gravity_well(mut gizmos: Gizmos, well: Res<GravityWell>) {
+    gizmos.circle_2d(well.center, GRAVITY_MIN_DISTANCE, Color::srgb(0.6, 0.3, 1.));
+}
+
+const WIND_PARTICLES: usize = 60;
+
+// Player velocity is recomputed from input every frame, so wind is a constant drift
+// on top of it, in units per second.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Wind(pub Vec2);
+
+pub fn apply_wind(mut query: Query<&mut Velocity, RollbackPlayer>, wind: Res<Wind>) {
+    for mut vel in query.iter_mut() {
+        vel.x += wind.0.x;
+        vel.y += wind.0.y;
+    }
+}
+
+// render-only dust blown across the arena, never part of the simulation
+pub fn draw_wind(
+    mut gizmos: Gizmos,
+    mut particles: Local<Vec<Vec2>>,
+    wind: Res<Wind>,
+    arena: Res<Arena>,
+    time: Res<Time>
+) {
+    let half = arena.half_size;
+    if particles.is_empty() {
+        let mut rng = SimRng::new(0);
+        *particles = (0..WIND_PARTICLES)
+            .map(|_| Vec2::new(rng.next_f32(), rng.next_f32()) * 2. * half - half)
+            .collect();
+    }
+    let streak = wind.0.normalize_or_zero() * 8.;
+    for p in particles.iter_mut() {
+        *p += wind.0 * time.delta_seconds();
+        *p = (*p + half).rem_euclid(2. * half) - half;
+        gizmos.line_2d(*p, *p + streak, Color::srgba(1., 1., 1., 0.3));
+    }
+}
+
+pub const DEFAULT_MUD_FACTOR: f32 = 0.5;
+
+// axis-aligned region scaling the speed of any player whose center is inside it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MudZone {
+    pub center: Vec2,
+    pub half_size: Vec2,
+    pub factor: f32,
+}
+
+impl MudZone {
+    pub fn contains(&self, pos: Vec2) -> bool {
+        let d = (pos - self.center).abs();
+        d.x <= self.half_size.x && d.y <= self.half_size.y
+    }
+}
+
+// overlapping zones don't stack, the slowest one wins
+pub fn mud_factor(pos: Vec2, zones: &[MudZone]) -> f32 {
+    zones.iter().filter(|z| z.contains(pos)).map(|z| z.factor).fold(1., f32::min)
+}
+
+pub const DEFAULT_ICE_GRIP: f32 = 0.05;
+
+// ice reuses the zone shape, its factor is the share of the gap to the input
+// velocity closed each frame, so players keep sliding after letting go
+pub fn ice_grip(pos: Vec2, zones: &[MudZone]) -> f32 {
+    mud_factor(pos, zones)
+}
+
+pub fn apply_mud(
+    mut query: Query<(&Transform, &Player, &mut Velocity), RollbackPlayer>,
+    fields: Query<&SlowField, With<Rollback>>,
+    map: Res<MapSpec>
+) {
+    if map.mud.is_empty() && fields.is_empty() {
+        return;
+    }
+    for (transform, player, mut vel) in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let deployed = fields.iter().filter(|f| f.owner != player.id).map(|f| f.zone);
+        let factor = mud_factor(pos, &map.mud).min(mud_factor(pos, &deployed.collect::<Vec<_>>()));
+        vel.x *= factor;
+        vel.y *= factor;
+    }
+}
+
+pub fn spawn_floor_zones(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    map: Res<MapSpec>
+) {
+    let material = materials.add(Color::srgba(0.45, 0.3, 0.15, 0.45));
+    for zone in &map.mud {
+        commands.spawn(MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(zone.half_size * 2.))),
+            material: material.clone(),
+            transform: Transform::from_translation(zone.center.extend(-2.)),
+            ..default()
+        });
+    }
+    let material = materials.add(Color::srgba(0.7, 0.9, 1., 0.35));
+    for zone in &map.ice {
+        commands.spawn(MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(zone.half_size * 2.))),
+            material: material.clone(),
+            transform: Transform::from_translation(zone.center.extend(-2.)),
+            ..default()
+        });
+    }
+}
+
+// eliminates any player whose center falls in, they come back at their spawn point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlackHole {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl BlackHole {
+    pub fn swallows(&self, pos: Vec2) -> bool {
+        pos.distance(self.center) < self.radius
+    }
+}
+
+type Swallowed = (
+    Entity,
+    &'static mut Transform,
+    &'static mut Velocity,
+    &'static mut Knockback,
+    &'static mut Pull,
+    &'static mut Health,
+    &'static mut Shield,
+    &'static SpawnPoint,
+    &'static Player,
+    &'static Team,
+    &'static KnockedOut,
+);
+
+// Swallowed players come back at whichever spawn point is farthest from the opponents
+// still in play, judged from where everyone was before anyone got moved this frame.
+pub fn swallow_players(
+    mut query: Query<Swallowed, RollbackPlayer>,
+    map: Res<MapSpec>,
+    arena: Res<Arena>,
+    wrap: Option<Res<Wrap>>,
+    mut events: ResMut<SimEvents>
+) {
+    if map.black_holes.is_empty() {
+        return;
+    }
+    let wrap = wrap.map(|_| arena.half_size);
+    let mut players: Vec<_> = query.iter_mut().collect();
+    players.sort_by_key(|(.., player, _, _)| player.id);
+    let spawns: Vec<_> = players.iter().map(|(.., spawn, _, _, _)| spawn.0).collect();
+    let standing: Vec<_> = players
+        .iter()
+        .filter(|(.., knocked_out)| !knocked_out.out)
+        .map(|(entity, transform, .., player, team, _)| (*entity, player.id, **team, transform.translation.truncate()))
+        .collect();
+
+    for (_, transform, vel, knockback, pull, health, shield, spawn, player, team, _) in players.iter_mut() {
+        let pos = transform.translation.truncate();
+        if !map.black_holes.iter().any(|hole| hole.swallows(pos)) {
+            continue;
+        }
+        let opponents: Vec<_> =
+            standing.iter().filter(|(.., t, _)| t != *team).map(|&(e, id, _, pos)| (e, id, pos)).collect();
+        let respawn = safest_spawn(&spawns, &opponents, wrap).unwrap_or(spawn.0);
+        transform.translation = respawn.extend(transform.translation.z);
+        events.0.push(SimEvent::Relocated { player: player.id, to: respawn });
+        **vel = Velocity { x: 0., y: 0. };
+        knockback.0 = Vec2::ZERO;
+        pull.0 = Vec2::ZERO;
+        **health = Health::new(health.max);
+        **shield = Shield::default();
+    }
+}
+
+pub fn draw_black_holes(mut gizmos: Gizmos, map: Res<MapSpec>, time: Res<Time>) {
+    let spin = time.elapsed_seconds() * 2.;
+    for hole in &map.black_holes {
+        gizmos.circle_2d(hole.center, hole.radius, Color::srgb(0.5, 0.1, 0.7));
+        // arms curling inward
+        for arm in 0..4 {
+            let start = spin + arm as f32 * std::f32::consts::FRAC_PI_2;
+            let points = (0..=12).map(|i| {
+                let t = i as f32 / 12.;
+                let angle = start + t * 3.;
+                hole.center + Vec2::from_angle(angle) * hole.radius * (1. - t)
+            });
+            gizmos.linestrip_2d(points, Color::srgba(0.7, 0.3, 1., 0.7));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Harness;
+    use crate::script::InputScript;
+    use crate::PLAYER_SPEED;
+
+    #[test]
+    fn gravity_points_at_the_well_and_falls_off_with_distance() {
+        let well = GravityWell { center: Vec2::new(100., 0.), strength: 1000. };
+        let near = gravity_acceleration(Vec2::new(0., 0.), &well);
+        assert_eq!(near, Vec2::new(10., 0.));
+        let far = gravity_acceleration(Vec2::new(100., -200.), &well);
+        assert_eq!(far, Vec2::new(0., 5.));
+        // capped inside the minimum distance, and nothing right on top of it
+        assert_eq!(gravity_acceleration(Vec2::new(90., 0.), &well), Vec2::new(20., 0.));
+        assert_eq!(gravity_acceleration(well.center, &well), Vec2::ZERO);
+    }
+
+    #[test]
+    fn wind_adds_to_every_players_velocity_each_frame() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 r").unwrap());
+        harness.app.insert_resource(Wind(Vec2::new(30., -10.)));
+        for frame in [1, 2, 20] {
+            harness.run_to(frame);
+            let players = harness.players();
+            assert_eq!((players[0].2.x, players[0].2.y), (PLAYER_SPEED + 30., -10.), "frame {frame}");
+            assert_eq!((players[1].2.x, players[1].2.y), (30., -10.), "frame {frame}");
+        }
+    }
+
+    #[test]
+    fn mud_slows_whoever_is_inside_it() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 r\n1 1 r").unwrap());
+        for (id, y) in [(0, 0.), (1, 200.)] {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(0., y, 0.);
+        }
+        let mud = MudZone { center: Vec2::ZERO, half_size: Vec2::new(300., 50.), factor: 0.25 };
+        harness.app.insert_resource(MapSpec { mud: vec![mud], ..default() });
+        harness.run_to(10);
+        let players = harness.players();
+        assert_eq!(players[0].2.x, PLAYER_SPEED * 0.25);
+        assert_eq!(players[1].2.x, PLAYER_SPEED);
+        assert_eq!(mud_factor(Vec2::new(0., 60.), &[mud]), 1.);
+    }
+
+    #[test]
+    fn walking_into_a_black_hole_respawns_the_player_once() {
+        // walks in within the first ten frames, then stops short of walking back in
+        let mut harness = Harness::new(1, InputScript::parse("1 0 r\n11 0 -").unwrap());
+        let player = harness.player(0);
+        let spawn = harness.app.world().get::<SpawnPoint>(player).unwrap().0;
+        let hole = BlackHole { center: spawn + Vec2::new(50., 0.), radius: 20. };
+        harness.app.insert_resource(MapSpec { black_holes: vec![hole], ..default() });
+        harness.app.world_mut().get_mut::<Health>(player).unwrap().damage(40);
+
+        let mut swallowed = Vec::new();
+        for frame in 1..=60 {
+            harness.run_to(frame);
+            let events = &harness.app.world().resource::<SimEvents>().0;
+            if events.contains(&SimEvent::Relocated { player: 0, to: spawn }) {
+                swallowed.push((frame, harness.players()[0].1));
+            }
+        }
+        let [(_, pos)] = swallowed[..] else {
+            panic!("swallowed on {swallowed:?}");
+        };
+        assert_eq!(pos, spawn);
+        let health = harness.app.world().get::<Health>(player).unwrap();
+        assert_eq!(health.hp, health.max);
+    }
+
+    #[test]
+    fn on_ice_a_player_keeps_sliding_after_letting_go() {
+        // running right for a second, then nothing pressed
+        let mut harness = Harness::new(2, InputScript::parse("1 0 r\n1 1 r\n61 0 -\n61 1 -").unwrap());
+        for (id, y) in [(0, 0.), (1, 200.)] {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(-500., y, 0.);
+        }
+        let ice = MudZone { center: Vec2::ZERO, half_size: Vec2::new(600., 50.), factor: DEFAULT_ICE_GRIP };
+        harness.app.insert_resource(MapSpec { ice: vec![ice], ..default() });
+        harness.run_to(60);
+        let players = harness.players();
+        assert!(players[0].2.x > PLAYER_SPEED * 0.9, "ice still speeds up, just slowly: {}", players[0].2.x);
+        assert_eq!(players[1].2.x, PLAYER_SPEED);
+
+        let held = players[0].2.x;
+        harness.run_to(61);
+        let players = harness.players();
+        let slide = players[0].2.x;
+        assert!((slide - held * (1. - DEFAULT_ICE_GRIP)).abs() < 1e-3, "{slide}");
+        assert_eq!(players[1].2.x, 0.);
+        harness.run_to(70);
+        let later = harness.players()[0].2.x;
+        assert!(0. < later && later < slide);
+    }
+
+    #[test]
+    fn a_player_standing_still_falls_toward_the_well() {
+        let mut harness = Harness::new(1, InputScript::default());
+        harness.app.insert_resource(GravityWell { center: Vec2::ZERO, strength: 30000. });
+        let player = harness.player(0);
+        harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(-300., 0., 0.);
+        let mut speeds = Vec::new();
+        for frame in [10, 30, 60] {
+            harness.run_to(frame);
+            let (_, pos, vel) = harness.players()[0];
+            assert_eq!((pos.y, vel.y), (0., 0.));
+            speeds.push(vel.x);
+        }
+        // the pull builds up, one frame's worth of it alone would be under 2 units per second
+        assert!(speeds.windows(2).all(|w| w[0] < w[1]), "{speeds:?}");
+        assert!(speeds[2] > 50., "{speeds:?}");
+        let pos = harness.players()[0].1;
+        assert!(pos.x > -270., "only got to {pos}");
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bytemuck::{Pod, Zeroable};
+use clap::ValueEnum;
+
+// Wire format of InputPacked. Peers only understand each other if they agree on it,
+// so any change to the layout below has to bump this. Every input carries it and a
+// session with a peer on another version is shut down, all peers need the same one.
+pub const PROTOCOL_VERSION: u8 = 5;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct InputPacked {
+    pub wasd: u8,
+    pub version: u8,
+}
+
+impl InputPacked {
+    pub fn new(wasd: u8) -> Self {
+        Self { wasd, version: PROTOCOL_VERSION }
+    }
+}
+
+// which bit of InputPacked::wasd carries which button
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputLayout {
+    Up = 0,
+    Down = 1,
+    Right = 2,
+    Left = 3,
+    Fire = 4,
+    Magnet = 5,
+    Hook = 6,
+    SlowField = 7,
+}
+
+impl InputLayout {
+    pub const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    pub fn is_set(self, wasd: u8) -> bool {
+        wasd & self.mask() != 0
+    }
+
+    pub fn pack(held: impl IntoIterator<Item = InputLayout>) -> u8 {
+        held.into_iter().fold(0, |wasd, button| wasd | button.mask())
+    }
+}
+
+const DEFAULT_KEYS: [(KeyCode, InputLayout); 8] = [
+    (KeyCode::ArrowUp, InputLayout::Up),
+    (KeyCode::ArrowDown, InputLayout::Down),
+    (KeyCode::ArrowRight, InputLayout::Right),
+    (KeyCode::ArrowLeft, InputLayout::Left),
+    (KeyCode::Space, InputLayout::Fire),
+    (KeyCode::ShiftLeft, InputLayout::Magnet),
+    (KeyCode::ControlLeft, InputLayout::Hook),
+    (KeyCode::AltLeft, InputLayout::SlowField),
+];
+
+#[derive(Resource, Clone, Debug)]
+pub struct KeyBindings(pub Vec<(KeyCode, InputLayout)>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings(DEFAULT_KEYS.to_vec())
+    }
+}
+
+#[derive(SystemParam)]
+pub struct BoundKeys<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    bindings: Res<'w, KeyBindings>,
+}
+
+impl BoundKeys<'_> {
+    pub fn pack(&self) -> u8 {
+        InputLayout::pack(self.bindings.0.iter().filter(|(key, _)| self.keys.pressed(*key)).map(|(_, button)| *button))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Remap {
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub swap_axes: bool,
+}
+
+// Per-handle movement remapping, applied before local inputs go on the wire so
+// every peer simulates what the remapped player meant.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct InputRemap(pub Vec<Remap>);
+
+impl InputRemap {
+    pub fn new(players: usize, invert_x: &[usize], invert_y: &[usize], swap_axes: &[usize]) -> Self {
+        InputRemap(
+            (0..players)
+                .map(|id| Remap {
+                    invert_x: invert_x.contains(&id),
+                    invert_y: invert_y.contains(&id),
+                    swap_axes: swap_axes.contains(&id),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn apply(&self, handle: usize, wasd: u8) -> u8 {
+        self.0.get(handle).map_or(wasd, |remap| remap_bits(wasd, *remap))
+    }
+}
+
+// swapping happens first, so inverting y flips the axis the player ends up moving on
+pub fn remap_bits(wasd: u8, remap: Remap) -> u8 {
+    let bit = |b: InputLayout| b.is_set(wasd);
+    let (mut up, mut down, mut right, mut left) =
+        (bit(InputLayout::Up), bit(InputLayout::Down), bit(InputLayout::Right), bit(InputLayout::Left));
+    if remap.swap_axes {
+        (up, down, right, left) = (right, left, up, down);
+    }
+    if remap.invert_x {
+        (right, left) = (left, right);
+    }
+    if remap.invert_y {
+        (up, down) = (down, up);
+    }
+    let movement = [(up, InputLayout::Up), (down, InputLayout::Down), (right, InputLayout::Right), (left, InputLayout::Left)];
+    let rest = wasd & !InputLayout::pack(movement.map(|(_, b)| b));
+    rest | InputLayout::pack(movement.into_iter().filter(|(held, _)| *held).map(|(_, b)| b))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Resource, ValueEnum)]
+pub enum ListInputs {
+    Exit,
+    Continue,
+}
+
+pub fn format_gamepads(gamepads: &[(usize, String)]) -> Vec<String> {
+    if gamepads.is_empty() {
+        return vec!["no gamepads connected".to_string()];
+    }
+    gamepads.iter().map(|(id, name)| format!("gamepad {id}: {name}")).collect()
+}
+
+pub fn format_bindings(bindings: &KeyBindings) -> Vec<String> {
+    bindings.0.iter().map(|(key, button)| format!("{key:?} -> {button:?}")).collect()
+}
+
+// gamepads show up through connection events on the first update, so this can't run at startup
+pub fn list_inputs(
+    gamepads: Res<Gamepads>,
+    bindings: Res<KeyBindings>,
+    mode: Res<ListInputs>,
+    mut exit: EventWriter<AppExit>,
+    mut done: Local<bool>
+) {
+    if *done {
+        return;
+    }
+    *done = true;
+    let pads: Vec<_> = gamepads
+        .iter()
+        .map(|pad| (pad.id, gamepads.name(pad).unwrap_or("unknown").to_string()))
+        .collect();
+    for line in format_gamepads(&pads).into_iter().chain(format_bindings(&bindings)) {
+        println!("{line}");
+    }
+    if *mode == ListInputs::Exit {
+        exit.send(AppExit::Success);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUTTONS: [InputLayout; 8] = [
+        InputLayout::Up,
+        InputLayout::Down,
+        InputLayout::Right,
+        InputLayout::Left,
+        InputLayout::Fire,
+        InputLayout::Magnet,
+        InputLayout::Hook,
+        InputLayout::SlowField,
+    ];
+
+    #[test]
+    fn every_bit_packs_and_unpacks_to_itself() {
+        for (bit, button) in BUTTONS.into_iter().enumerate() {
+            assert_eq!(button.mask(), 1 << bit, "{button:?}");
+            assert_eq!(BUTTONS.map(|b| b.is_set(button.mask())), std::array::from_fn(|i| i == bit));
+        }
+        for wasd in 0..=u8::MAX {
+            let held = BUTTONS.into_iter().filter(|b| b.is_set(wasd));
+            assert_eq!(InputLayout::pack(held), wasd);
+        }
+        // and the input goes over the wire as exactly these two bytes
+        let input = InputPacked::new(0b1001_0110);
+        assert_eq!(bytemuck::bytes_of(&input), [0b1001_0110, PROTOCOL_VERSION]);
+        assert_eq!(*bytemuck::from_bytes::<InputPacked>(&[0b1001_0110, PROTOCOL_VERSION]), input);
+    }
+
+    #[test]
+    fn gamepads_and_bindings_list_one_per_line() {
+        assert_eq!(format_gamepads(&[]), ["no gamepads connected"]);
+        let pads = [(0, "Xbox Controller".to_string()), (3, "DualSense".to_string())];
+        assert_eq!(format_gamepads(&pads), ["gamepad 0: Xbox Controller", "gamepad 3: DualSense"]);
+        let bindings = KeyBindings(vec![(KeyCode::KeyW, InputLayout::Up), (KeyCode::Space, InputLayout::Fire)]);
+        assert_eq!(format_bindings(&bindings), ["KeyW -> Up", "Space -> Fire"]);
+    }
+
+    #[test]
+    fn inverting_y_turns_up_into_down_for_that_player_only() {
+        let remap = InputRemap::new(2, &[], &[0], &[]);
+        let up_fire = InputLayout::pack([InputLayout::Up, InputLayout::Fire]);
+        assert_eq!(remap.apply(0, up_fire), InputLayout::pack([InputLayout::Down, InputLayout::Fire]));
+        assert_eq!(remap.apply(1, up_fire), up_fire);
+        // handles past the configured players are left alone
+        assert_eq!(remap.apply(5, up_fire), up_fire);
+
+        let swapped = Remap { swap_axes: true, invert_y: true, ..default() };
+        assert_eq!(remap_bits(InputLayout::Right.mask(), swapped), InputLayout::Down.mask());
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use crate::sim::FrameCounter;
+use crate::UPS;
+
+const INTERPOLATION_KEY: KeyCode = KeyCode::F4;
+// moves longer than this in one frame (portals, respawns) snap instead of sliding across
+const SNAP_DISTANCE: f32 = 100.;
+
+// Render-only smoothing: players are drawn between their last two simulated positions,
+// a frame behind, instead of jumping once per simulated frame. The simulation never reads it.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct Interpolation {
+    pub enabled: bool,
+}
+
+// the last two simulated positions seen by the renderer
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Interpolated {
+    from: Vec2,
+    to: Vec2,
+}
+
+pub fn interpolate(from: Vec2, to: Vec2, alpha: f32) -> Vec2 {
+    if from.distance(to) > SNAP_DISTANCE {
+        return to;
+    }
+    from.lerp(to, alpha.clamp(0., 1.))
+}
+
+pub fn toggle_interpolation(input: Res<ButtonInput<KeyCode>>, mut interpolation: ResMut<Interpolation>) {
+    if input.just_pressed(INTERPOLATION_KEY) {
+        interpolation.enabled = !interpolation.enabled;
+        info!("interpolation {}", if interpolation.enabled { "on" } else { "off" });
+    }
+}
+
+pub fn track_interpolation(
+    mut query: Query<(&Transform, &mut Interpolated)>,
+    frame: Res<FrameCounter>,
+    mut last: Local<u32>
+) {
+    if frame.0 == *last {
+        return;
+    }
+    *last = frame.0;
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.from = interpolated.to;
+        interpolated.to = transform.translation.truncate();
+    }
+}
+
+// runs after transform propagation, so only what gets drawn moves
+pub fn apply_interpolation(
+    mut query: Query<(&Transform, &Interpolated, &mut GlobalTransform)>,
+    interpolation: Res<Interpolation>,
+    frame: Res<FrameCounter>,
+    time: Res<Time>,
+    // last frame seen and seconds since it was simulated
+    mut since: Local<(u32, f32)>
+) {
+    if !interpolation.enabled {
+        return;
+    }
+    if frame.0 == since.0 {
+        since.1 += time.delta_seconds();
+    } else {
+        *since = (frame.0, 0.);
+    }
+    let alpha = since.1 * UPS;
+    for (transform, interpolated, mut global) in query.iter_mut() {
+        let pos = interpolate(interpolated.from, interpolated.to, alpha);
+        *global = GlobalTransform::from(Transform { translation: pos.extend(transform.translation.z), ..*transform });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+
+    #[test]
+    fn the_key_flips_interpolation_on_and_off() {
+        let mut world = World::new();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<Interpolation>();
+        let enabled = |world: &World| world.resource::<Interpolation>().enabled;
+
+        world.run_system_once(toggle_interpolation);
+        assert!(!enabled(&world));
+        world.resource_mut::<ButtonInput<KeyCode>>().press(INTERPOLATION_KEY);
+        world.run_system_once(toggle_interpolation);
+        assert!(enabled(&world));
+        // held down into the next frame doesn't keep flipping it
+        world.resource_mut::<ButtonInput<KeyCode>>().clear();
+        world.run_system_once(toggle_interpolation);
+        assert!(enabled(&world));
+
+        let mut keys = world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(INTERPOLATION_KEY);
+        keys.clear();
+        keys.press(INTERPOLATION_KEY);
+        world.run_system_once(toggle_interpolation);
+        assert!(!enabled(&world));
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use crate::combat::Health;
+use crate::map::MapSpec;
+use crate::projectile::Ammo;
+use crate::sim::FrameCounter;
+use crate::{Player, PLAYER_RADIUS};
+
+const ITEM_RADIUS: f32 = 12.;
+const ITEM_RESPAWN_FRAMES: u32 = 600;
+const HEAL_AMOUNT: i32 = 25;
+const SPEED_BOOST_FRAMES: u32 = 180;
+pub const SPEED_BOOST_FACTOR: f32 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Heal,
+    Speed,
+    Ammo,
+}
+
+impl ItemKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "heal" => Some(ItemKind::Heal),
+            "speed" => Some(ItemKind::Speed),
+            "ammo" => Some(ItemKind::Ammo),
+            _ => None,
+        }
+    }
+}
+
+// `spot` indexes the map's item list, which also orders pickups between peers
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub spot: usize,
+}
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SpeedBoost {
+    pub frames: u32,
+}
+
+impl SpeedBoost {
+    // speed multiplier for this frame
+    pub fn tick(&mut self) -> f32 {
+        let boosted = self.frames > 0;
+        self.frames = self.frames.saturating_sub(1);
+        if boosted { SPEED_BOOST_FACTOR } else { 1. }
+    }
+}
+
+// map spots whose item was taken, with the frame it comes back on
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ItemRespawns(pub Vec<(usize, u32)>);
+
+fn item_bundle(map: &MapSpec, spot: usize) -> (Item, Transform) {
+    let (pos, kind) = map.items[spot];
+    (Item { kind, spot }, Transform::from_translation(pos.extend(0.5)))
+}
+
+pub fn spawn_items(mut commands: Commands, map: Res<MapSpec>) {
+    for spot in 0..map.items.len() {
+        commands.spawn(item_bundle(&map, spot)).add_rollback();
+    }
+}
+
+pub fn apply_item(kind: ItemKind, health: &mut Health, boost: &mut SpeedBoost, ammo: &mut Ammo) {
+    match kind {
+        ItemKind::Heal => health.heal(HEAL_AMOUNT),
+        ItemKind::Speed => boost.frames = SPEED_BOOST_FRAMES,
+        ItemKind::Ammo => ammo.refill(),
+    }
+}
+
+pub fn pick_up_items(
+    mut commands: Commands,
+    items: Query<(Entity, &Transform, &Item), With<Rollback>>,
+    mut players: Query<(&Transform, &Player, &mut Health, &mut SpeedBoost, &mut Ammo), With<Rollback>>,
+    mut respawns: ResMut<ItemRespawns>,
+    frame: Res<FrameCounter>
+) {
+    let mut items: Vec<_> = items.iter().collect();
+    items.sort_by_key(|(_, _, item)| item.spot);
+    let mut players: Vec<_> = players.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.id);
+
+    for (entity, transform, item) in items {
+        let pos = transform.translation.truncate();
+        let taker = players
+            .iter_mut()
+            .find(|(t, ..)| pos.distance(t.translation.truncate()) < ITEM_RADIUS + PLAYER_RADIUS);
+        if let Some((_, _, health, boost, ammo)) = taker {
+            apply_item(item.kind, health, boost, ammo);
+            commands.entity(entity).despawn();
+            respawns.0.push((item.spot, frame.0 + ITEM_RESPAWN_FRAMES));
+        }
+    }
+}
+
+pub fn respawn_items(
+    mut commands: Commands,
+    mut respawns: ResMut<ItemRespawns>,
+    map: Res<MapSpec>,
+    frame: Res<FrameCounter>
+) {
+    let mut due = Vec::new();
+    respawns.0.retain(|&(spot, at)| {
+        let ready = frame.0 >= at;
+        if ready {
+            due.push(spot);
+        }
+        !ready
+    });
+    for spot in due {
+        commands.spawn(item_bundle(&map, spot)).add_rollback();
+    }
+}
+
+pub fn add_item_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(Entity, &Item), Without<Mesh2dHandle>>
+) {
+    for (entity, item) in query.iter() {
+        let (mesh, color) = match item.kind {
+            ItemKind::Heal => (meshes.add(Rectangle::from_length(ITEM_RADIUS * 1.6)), Color::srgb(0.2, 0.9, 0.3)),
+            ItemKind::Speed => (meshes.add(RegularPolygon::new(ITEM_RADIUS, 3)), Color::srgb(1., 0.85, 0.1)),
+            ItemKind::Ammo => (meshes.add(Rectangle::new(ITEM_RADIUS * 0.8, ITEM_RADIUS * 1.8)), Color::srgb(0.9, 0.5, 0.2)),
+        };
+        commands.entity(entity).insert((
+            Mesh2dHandle(mesh),
+            materials.add(color),
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::{spawn_rollback, Harness};
+    use crate::script::InputScript;
+
+    #[test]
+    fn standing_on_an_item_takes_it_once() {
+        let mut harness = Harness::new(1, InputScript::default());
+        let player = harness.player(0);
+        let world = harness.app.world_mut();
+        let pos = world.get::<Transform>(player).unwrap().translation.truncate();
+        let map = MapSpec { items: vec![(pos, ItemKind::Heal)], ..default() };
+        spawn_rollback(world, item_bundle(&map, 0));
+        world.insert_resource(map);
+        world.get_mut::<Health>(player).unwrap().damage(50);
+        let hp = world.get::<Health>(player).unwrap().hp;
+
+        // rolled back and re-simulated every frame, it still only heals once
+        harness.run_to(30);
+        let world = harness.app.world_mut();
+        assert_eq!(world.get::<Health>(player).unwrap().hp, hp + HEAL_AMOUNT);
+        assert_eq!(world.query::<&Item>().iter(world).count(), 0);
+        assert_eq!(world.resource::<ItemRespawns>().0, [(0, 1 + ITEM_RESPAWN_FRAMES)]);
+    }
+}
@@ -1,33 +1,64 @@
+use std::error::Error;
+use std::fs;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use bevy::app::{PluginGroupBuilder, ScheduleRunnerPlugin};
 use bevy::color::Color;
 use bevy::DefaultPlugins;
+use bevy::ecs::system::{EntityCommands, RunSystemOnce};
 use bevy::prelude::*;
 use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle, Wireframe2dPlugin};
 use bevy::time::Time;
-use bevy::input::ButtonInput;
+use bevy::time::TimeSystem;
 use bevy::utils::HashMap;
-use bevy_ggrs::{AddRollbackCommandExtension, GgrsApp, GgrsConfig, GgrsPlugin, GgrsSchedule, LocalInputs, LocalPlayers, PlayerInputs, ReadInputs, Rollback, Session};
+use bevy::window::{ExitCondition, PresentMode};
+use bevy::winit::WinitPlugin;
+use bevy_ggrs::{AddRollbackCommandExtension, AdvanceWorld, AdvanceWorldSet, GgrsApp, GgrsConfig, GgrsPlugin, GgrsSchedule, LocalInputs, LocalPlayers, PlayerInputs, ReadInputs, Rollback, RollbackFrameCount, Session};
 use bevy_ggrs::ggrs::{PlayerType, UdpNonBlockingSocket};
 use bevy_ggrs::prelude::SessionBuilder;
-use bytemuck::{Pod, Zeroable};
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+use abilities::{Grapple, Magnet, SlowField};
+use collision::{CollisionLayer, Knockback, Mass};
+use combat::{Combo, Health, Shield, Stun};
+use items::{Item, ItemRespawns, SpeedBoost};
+use input::{BoundKeys, InputPacked, InputRemap, KeyBindings, PROTOCOL_VERSION};
+use projectile::{Ammo, Charge, Projectile, ProjectileConfig};
+use sim::{Cooldown, Countdown, FrameCounter, FrameHash, SimHasher};
+use teams::{Score, Team};
+
+mod abilities;
+mod arena;
+mod bots;
+mod camera;
+mod collision;
+mod combat;
+mod debug;
+mod hazards;
+#[cfg(test)]
+mod harness;
+mod input;
+mod interpolation;
+mod items;
+mod map;
+mod modes;
+mod net;
+mod particles;
+mod projectile;
+mod replay;
+mod rng;
+mod script;
+mod sim;
+mod teams;
+mod ui;
 
 const PLAYER_SPEED: f32 = 200.;
+const PLAYER_RADIUS: f32 = 25.;
 const UPS: f32 = 60.;
 static SPU: f32 = 1. / UPS;
 
 
-const INPUT_UP: u8 = 0;
-const INPUT_DOWN: u8 = 1;
-const INPUT_RIGHT: u8 = 2;
-const INPUT_LEFT: u8 = 4;
-
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Pod, Zeroable)]
-struct InputPacked {
-    wasd: u8
-}
 type Config = GgrsConfig<InputPacked>;
 
 #[derive(Clone, Copy, Component)]
@@ -41,41 +72,102 @@ struct Player {
     id: usize
 }
 
+type RollbackPlayer = (With<Player>, With<Rollback>);
+type PlayerMovement = (
+    &'static mut Velocity,
+    &'static mut Facing,
+    &'static Speed,
+    &'static mut Stun,
+    &'static mut SpeedBoost,
+    &'static mut Knockback,
+    &'static Player,
+    &'static Team,
+    &'static Transform,
+);
+
+#[derive(Clone, Copy, Component)]
+struct Facing(Vec2);
+
+#[derive(Clone, Copy, Component)]
+struct Speed(f32);
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed(PLAYER_SPEED)
+    }
+}
+
+#[derive(Clone, Copy, Component, Default)]
+struct LastInput {
+    wasd: u8,
+}
+
 fn read_local_inputs(
     mut commands: Commands,
-    input: Res<ButtonInput<KeyCode>>,
-    local_players: Res<LocalPlayers>
+    keys: BoundKeys,
+    remap: Res<InputRemap>,
+    local_players: Res<LocalPlayers>,
+    bots: Res<bots::Bots>,
+    frame: Res<RollbackFrameCount>,
+    players: Query<(Entity, &Transform, &Player)>
 ) {
     let mut local_inputs = HashMap::new();
+    let positions: Vec<_> = players.iter().map(|(e, t, p)| (e, p.id, t.translation.truncate())).collect();
 
     for id in &local_players.0 {
-        let wasd: u8 =
-            (input.pressed(KeyCode::ArrowUp) as u8) |
-            ((input.pressed(KeyCode::ArrowDown) as u8) << 1u8) |
-            ((input.pressed(KeyCode::ArrowRight) as u8) << 2u8) |
-            ((input.pressed(KeyCode::ArrowLeft) as u8) << 3u8);
-        local_inputs.insert(*id, InputPacked{wasd});
+        let wasd = if bots.controls(*id) {
+            bots::bot_input(&bots, *id, frame.0, &positions).wasd
+        } else {
+            keys.pack()
+        };
+        local_inputs.insert(*id, InputPacked::new(remap.apply(*id, wasd)));
     }
 
     commands.insert_resource(LocalInputs::<Config>(local_inputs));
 }
 
 fn handle_players(
-    mut query: Query<(&mut Velocity, &Player), With<Rollback>>,
-    inputs: Res<PlayerInputs<Config>>
+    mut query: Query<PlayerMovement, With<Rollback>>,
+    inputs: Res<PlayerInputs<Config>>,
+    countdown: Res<Countdown>,
+    frame: Res<FrameCounter>,
+    score: Res<Score>,
+    handicap: Option<Res<teams::Handicap>>,
+    map: Res<map::MapSpec>
 ) {
-    for (mut vel, player) in query.iter_mut() {
-        let wasd = inputs[player.id].0.wasd;
-
-        vel.y = (((wasd >> 0) & 1) as i32 - ((wasd >> 1) & 1) as i32) as f32 * PLAYER_SPEED;
-        vel.x = (((wasd >> 2) & 1) as i32 - ((wasd >> 3) & 1) as i32) as f32 * PLAYER_SPEED;
+    let frozen = countdown.active(frame.0);
+    for (mut vel, mut facing, speed, mut stun, mut boost, mut knockback, player, team, transform) in query.iter_mut() {
+        let stunned = stun.tick();
+        let wasd = if frozen || stunned { 0 } else { inputs[player.id].0.wasd };
+        let handicap = if handicap.is_some() { teams::handicap_factor(&score, *team) } else { 1. };
+        let input_vel = sim::compute_velocity(wasd, speed.0 * boost.tick() * handicap);
+        let dir = Vec2::new(input_vel.x, input_vel.y);
+        if dir != Vec2::ZERO {
+            facing.0 = dir.normalize();
+        }
+        let bump = knockback.tick();
+        let target = Vec2::new(input_vel.x + bump.x, input_vel.y + bump.y);
+        let grip = hazards::ice_grip(transform.translation.truncate(), &map.ice);
+        let new_vel = if grip < 1. { Vec2::new(vel.x, vel.y).lerp(target, grip) } else { target };
+        *vel = Velocity { x: new_vel.x, y: new_vel.y };
     }
 }
 
-fn velocity_system(mut query: Query<(&mut Transform, &Velocity), With<Rollback>>) {
-    for (mut transform, vel) in query.iter_mut() {
-        transform.translation.x += vel.x * SPU;
-        transform.translation.y += vel.y * SPU;
+type Moving = (&'static mut Transform, &'static mut Velocity, Option<&'static mut sim::PrevPosition>, Has<Player>);
+
+// only players are capped, projectiles keep whatever speed they were fired at
+fn velocity_system(mut query: Query<Moving, With<Rollback>>, mut hasher: SimHasher) {
+    for (mut transform, mut vel, prev, player) in query.iter_mut() {
+        if player {
+            *vel = sim::clamp_speed(*vel, sim::MAX_SPEED);
+        }
+        if let Some(mut prev) = prev {
+            prev.0 = transform.translation.truncate();
+        }
+        transform.translation = sim::integrate(transform.translation, *vel, SPU);
+        hasher.hash_vec2(transform.translation.truncate());
+        hasher.hash_f32(vel.x);
+        hasher.hash_f32(vel.y);
     }
 }
 
@@ -83,7 +175,9 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    session: Res<Session<Config>>
+    session: Res<Session<Config>>,
+    arena: Res<arena::Arena>,
+    opt: Res<Opt>
 ) {
     let players_num = match &*session {
         Session::SyncTest(s) => s.num_players(),
@@ -91,76 +185,1001 @@ fn setup(
         Session::Spectator(s) => s.num_players(),
     };
 
-    let mesh = meshes.add(Circle::new(25.));
+    let mesh = meshes.add(Circle::new(PLAYER_RADIUS));
 
-    for i in 0..players_num {
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(mesh.clone()),
-                material: materials.add(Color::hsv(1., 1., 1. / (players_num * i) as f32)),
-                transform: Transform::from_translation(Vec3{x: (100isize - (100isize * players_num as isize) + (200isize * i as isize)) as f32, y: 0., z: 0.}),
-                ..default()
-            },
+    let spawns = arena::spawn_positions(opt.spawn, opt.seed, players_num, arena.half_size);
+    for (i, pos) in spawns.into_iter().enumerate() {
+        spawn_player(&mut commands, i, pos).insert(MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(mesh.clone()),
+            material: materials.add(ui::player_color(&opt.colors, opt.palette, i, players_num)),
+            transform: Transform::from_translation(pos.extend(0.)),
+            ..default()
+        });
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+// everything the simulation needs on a player, `setup` adds how it looks on top
+fn player_bundle(id: usize, pos: Vec2) -> impl Bundle {
+    (
+        (
+            (Transform::from_translation(pos.extend(0.)), sim::PrevPosition(pos)),
             Velocity {
                 x: 0.,
                 y: 0.
             },
             Player {
-              id: i
-            }
-            )).add_rollback();
+              id
+            },
+            Facing(Vec2::X),
+            Speed::default(),
+            LastInput::default(),
+            Mass::default(),
+            Knockback::default(),
+            hazards::Pull::default(),
+            Grapple::default(),
+            Cooldown::<SlowField>::new(abilities::SLOW_FIELD_COOLDOWN),
+            interpolation::Interpolated::default(),
+            modes::KnockedOut::default(),
+            modes::Idle::default(),
+            CollisionLayer::player()
+        ),
+        (
+            Charge::default(),
+            Cooldown::<arena::PortalUse>::new(arena::PORTAL_COOLDOWN),
+            Cooldown::<arena::BouncePad>::new(arena::PAD_COOLDOWN),
+            Cooldown::<projectile::Shot>::new(projectile::FIRE_COOLDOWN),
+            Ammo::default(),
+            Combo::default(),
+            Magnet::default(),
+            Cooldown::<Magnet>::new(abilities::MAGNET_COOLDOWN),
+            Health::default(),
+            Shield::default(),
+            arena::SpawnPoint(pos),
+            Stun::default(),
+            SpeedBoost::default(),
+            Team::of_player(id),
+            modes::Tagged::default(),
+        ),
+    )
+}
+
+fn spawn_player<'a>(commands: &'a mut Commands, id: usize, pos: Vec2) -> EntityCommands<'a> {
+    let mut player = commands.spawn(player_bundle(id, pos));
+    player.add_rollback();
+    player
+}
+
+// Puts the simulation back to how it was before frame 1: players fresh on their spawn
+// points, everything else the frames spawned gone, items and the flag back in place.
+// Only the rollback state is touched, how things look is added back by the visual systems.
+pub fn restart_simulation(world: &mut World) {
+    let mut players = world.query_filtered::<(Entity, &Player, &arena::SpawnPoint), With<Rollback>>();
+    let players: Vec<_> = players.iter(world).map(|(e, p, spawn)| (e, p.id, spawn.0)).collect();
+    for (entity, id, spawn) in players {
+        world.entity_mut(entity).insert(player_bundle(id, spawn));
+    }
+    let mut others = world.query_filtered::<Entity, (With<Rollback>, Without<Player>)>();
+    for entity in others.iter(world).collect::<Vec<_>>() {
+        world.entity_mut(entity).despawn_recursive();
+    }
+    world.run_system_once(items::spawn_items);
+    if world.contains_resource::<modes::Ctf>() {
+        world.run_system_once(modes::spawn_flag);
     }
 
-    commands.spawn(Camera2dBundle::default());
+    world.insert_resource(FrameCounter::default());
+    world.insert_resource(FrameHash::default());
+    world.insert_resource(sim::SimEvents::default());
+    world.insert_resource(ItemRespawns::default());
+    world.insert_resource(Score::default());
+    if let Some(mut energy) = world.get_resource_mut::<teams::TeamEnergy>() {
+        *energy = teams::TeamEnergy::new(energy.regen_per_second);
+    }
 }
 
 #[derive(Parser, Resource)]
 struct Opt {
-    #[clap(short, long)]
+    #[clap(short, long, default_value_t = 7000)]
     local_port: u16,
     #[clap(short, long, num_args = 1..)]
     players: Vec<String>,
+    #[clap(long)]
+    synctest: bool,
+    #[clap(long, num_args = 1.., value_name = "ADDR")]
+    spectators: Vec<SocketAddr>,
+    #[clap(long, value_name = "HOST")]
+    spectate: Option<SocketAddr>,
+    #[clap(long, default_value_t = 2, value_name = "N")]
+    spectate_players: usize,
+    #[clap(long, value_name = "FRAMES")]
+    check_distance: Option<usize>,
+    #[clap(long, default_value_t = 4)]
+    slowmo_factor: u32,
+    #[clap(long, value_name = "N")]
+    demo: Option<usize>,
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+    #[clap(long, value_enum, default_value_t = arena::SpawnLayout::Row)]
+    spawn: arena::SpawnLayout,
+    #[clap(long, value_enum, default_value_t = bots::BotBehavior::Wander)]
+    bot_behavior: bots::BotBehavior,
+    #[clap(long, default_value_t = 400.)]
+    projectile_speed: f32,
+    #[clap(long, default_value_t = 6.)]
+    projectile_radius: f32,
+    #[clap(long, default_value_t = 0, value_name = "BOUNCES")]
+    ricochets: u32,
+    #[clap(long, value_enum, default_value_t = projectile::Weapon::Projectile)]
+    weapon: projectile::Weapon,
+    #[clap(long, default_value_t = 0, value_name = "SECS")]
+    countdown: u32,
+    #[clap(long)]
+    input_delay: Option<usize>,
+    #[clap(long)]
+    no_prediction: bool,
+    #[clap(long, default_value_t = 50., value_name = "MS")]
+    expected_rtt: f32,
+    #[clap(long, default_value_t = 0, value_name = "MS")]
+    fake_latency: u64,
+    #[clap(long, default_value_t = 0, value_name = "MS")]
+    fake_jitter: u64,
+    #[clap(long)]
+    show_input_delay: bool,
+    #[clap(long, value_name = "PATH")]
+    map: Option<PathBuf>,
+    #[clap(long, default_value_t = 1200.)]
+    arena_width: f32,
+    #[clap(long, default_value_t = 680.)]
+    arena_height: f32,
+    #[clap(long)]
+    fit_window: bool,
+    #[clap(long)]
+    wrap: bool,
+    #[clap(long, value_name = "STRENGTH")]
+    gravity: Option<f32>,
+    #[clap(long, value_enum, default_value_t = sim::Integrator::SemiImplicit)]
+    integrator: sim::Integrator,
+    #[clap(long, value_name = "LENGTH")]
+    tether: Option<f32>,
+    #[clap(long, default_value_t = 0., allow_negative_numbers = true)]
+    wind_x: f32,
+    #[clap(long, default_value_t = 0., allow_negative_numbers = true)]
+    wind_y: f32,
+    #[clap(long, value_name = "N")]
+    max_frames: Option<u32>,
+    #[clap(long, value_name = "FRAMES")]
+    input_history: Option<usize>,
+    #[clap(long, value_name = "SECS")]
+    frame_report: Option<f32>,
+    #[clap(long, num_args = 1.., value_name = "PLAYER")]
+    invert_x: Vec<usize>,
+    #[clap(long, num_args = 1.., value_name = "PLAYER")]
+    invert_y: Vec<usize>,
+    #[clap(long, num_args = 1.., value_name = "PLAYER")]
+    swap_axes: Vec<usize>,
+    #[clap(long, value_enum, num_args = 0..=1, default_missing_value = "exit")]
+    list_inputs: Option<input::ListInputs>,
+    #[clap(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+    #[clap(long, value_enum, default_value_t = replay::ReplayFormat::Text)]
+    record_format: replay::ReplayFormat,
+    #[clap(long, value_name = "PATH")]
+    ghost: Option<PathBuf>,
+    #[clap(long, value_name = "PATH", conflicts_with_all = ["check_distance", "demo", "script"])]
+    play: Option<PathBuf>,
+    #[clap(long, num_args = 2, value_names = ["A", "B"])]
+    diff: Vec<PathBuf>,
+    #[clap(long)]
+    dump_state: bool,
+    #[clap(long, value_name = "PATH")]
+    script: Option<PathBuf>,
+    #[clap(long, value_name = "PATH", requires = "max_frames")]
+    expect: Option<PathBuf>,
+    #[clap(long)]
+    no_window: bool,
+    #[clap(long, value_enum, default_value_t = Vsync::On)]
+    vsync: Vsync,
+    #[clap(long, num_args = 1.., value_name = "HEX", value_parser = ui::parse_hex_color)]
+    colors: Vec<Color>,
+    #[clap(long, value_enum, default_value_t = ui::Palette::Default)]
+    palette: ui::Palette,
+    #[clap(long)]
+    freeze_tag: bool,
+    #[clap(long, conflicts_with = "wrap")]
+    sumo: bool,
+    #[clap(long)]
+    anti_camping: bool,
+    #[clap(long, value_enum, default_value_t = collision::TeamCollision::On)]
+    team_collision: collision::TeamCollision,
+    #[clap(long)]
+    handicap: bool,
+    #[clap(long, value_name = "PER_SEC")]
+    team_energy: Option<u32>,
+    #[clap(long, value_name = "SECS")]
+    safe_zone: Option<f32>,
+    #[clap(long)]
+    follow_camera: bool,
+    #[clap(long)]
+    interpolate: bool,
+    #[clap(long)]
+    offscreen_indicators: bool,
+    #[clap(long)]
+    nearest_indicator: bool,
+    #[clap(long)]
+    health_colors: bool,
+    #[clap(long)]
+    debug_velocity: bool,
+    #[clap(long)]
+    log_events: bool,
+    #[clap(long)]
+    no_particles: bool,
+    #[clap(long, default_value_t = 0.25)]
+    velocity_gizmo_scale: f32,
 }
 
-fn main() {
-    let opt = Opt::parse();
-    let players_num = opt.players.len();
-    assert!(players_num > 0);
+// only how often frames are drawn, the simulation keeps its fixed rate either way
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Vsync {
+    On,
+    Off,
+}
 
+impl Vsync {
+    fn present_mode(self) -> PresentMode {
+        match self {
+            Vsync::On => PresentMode::AutoVsync,
+            Vsync::Off => PresentMode::AutoNoVsync,
+        }
+    }
+}
+
+// the rollback state and every system of a simulated frame, the test harness runs
+// exactly this without any window, assets or network
+fn add_simulation(app: &mut App) -> &mut App {
+    app
+        .set_rollback_schedule_fps(UPS as usize)
+        .rollback_component_with_clone::<Transform>()
+        .rollback_component_with_copy::<Velocity>()
+        .rollback_component_with_copy::<sim::PrevPosition>()
+        .rollback_component_with_copy::<Facing>()
+        .rollback_component_with_copy::<Speed>()
+        .rollback_component_with_copy::<Mass>()
+        .rollback_component_with_copy::<Knockback>()
+        .rollback_component_with_copy::<hazards::Pull>()
+        .rollback_component_with_copy::<Cooldown<arena::PortalUse>>()
+        .rollback_component_with_copy::<Cooldown<arena::BouncePad>>()
+        .rollback_component_with_copy::<Cooldown<projectile::Shot>>()
+        .rollback_component_with_copy::<Health>()
+        .rollback_component_with_copy::<Team>()
+        .rollback_component_with_copy::<modes::Tagged>()
+        .rollback_component_with_copy::<modes::KnockedOut>()
+        .rollback_component_with_copy::<modes::Idle>()
+        .rollback_component_with_copy::<modes::Flag>()
+        .checksum_component_with_hash::<modes::Flag>()
+        .checksum_component_with_hash::<modes::Tagged>()
+        .checksum_component_with_hash::<modes::KnockedOut>()
+        .checksum_component_with_hash::<modes::Idle>()
+        .checksum_component_with_hash::<Health>()
+        .rollback_component_with_copy::<SpeedBoost>()
+        .rollback_component_with_copy::<Item>()
+        .checksum_component_with_hash::<Item>()
+        .rollback_component_with_copy::<Stun>()
+        .rollback_component_with_copy::<Shield>()
+        .checksum_component_with_hash::<Shield>()
+        .checksum_component_with_hash::<Stun>()
+        .rollback_component_with_copy::<LastInput>()
+        .rollback_component_with_copy::<Charge>()
+        .checksum_component_with_hash::<Charge>()
+        .rollback_component_with_copy::<Ammo>()
+        .checksum_component_with_hash::<Ammo>()
+        .rollback_component_with_copy::<Combo>()
+        .checksum_component_with_hash::<Combo>()
+        .rollback_component_with_copy::<Magnet>()
+        .checksum_component_with_hash::<Magnet>()
+        .rollback_component_with_copy::<Cooldown<Magnet>>()
+        .rollback_component_with_copy::<Grapple>()
+        .rollback_component_with_copy::<SlowField>()
+        .rollback_component_with_copy::<Cooldown<SlowField>>()
+        .rollback_component_with_copy::<CollisionLayer>()
+        .rollback_component_with_copy::<Projectile>()
+        .rollback_component_with_copy::<projectile::Beam>()
+        .rollback_component_with_copy::<projectile::Homing>()
+        .init_resource::<FrameCounter>()
+        .rollback_resource_with_copy::<FrameCounter>()
+        .checksum_resource_with_hash::<FrameCounter>()
+        .init_resource::<FrameHash>()
+        .checksum_resource_with_hash::<FrameHash>()
+        .init_resource::<sim::SimEvents>()
+        .rollback_resource_with_clone::<sim::SimEvents>()
+        .init_resource::<ItemRespawns>()
+        .rollback_resource_with_clone::<ItemRespawns>()
+        .checksum_resource_with_hash::<ItemRespawns>()
+        .init_resource::<Score>()
+        .rollback_resource_with_copy::<Score>()
+        .checksum_resource_with_hash::<Score>()
+        .add_systems(
+            AdvanceWorld,
+            replay::apply_playback_seek.in_set(AdvanceWorldSet::First).run_if(resource_exists::<replay::Playback>),
+        )
+        .add_systems(GgrsSchedule, (
+            (sim::advance_frame_counter, net::reject_protocol_mismatch).chain(),
+            arena::apply_arena_resizes.run_if(resource_exists::<arena::ArenaResizes>),
+            arena::move_walls,
+            (
+                velocity_system.run_if(sim::explicit_euler),
+                handle_players,
+                abilities::deploy_slow_fields,
+                hazards::apply_mud,
+                modes::slow_flag_carriers.run_if(resource_exists::<modes::Ctf>),
+                modes::freeze_tag.run_if(resource_exists::<modes::FreezeTag>),
+                modes::knock_off_edge.run_if(resource_exists::<modes::Sumo>),
+                projectile::fire_projectiles,
+                projectile::steer_homing,
+                abilities::use_magnets,
+                abilities::use_grapples,
+            ).chain(),
+            (
+                hazards::apply_gravity.run_if(resource_exists::<hazards::GravityWell>),
+                hazards::apply_wind.run_if(resource_exists::<hazards::Wind>),
+                velocity_system.run_if(not(sim::explicit_euler)),
+            ).chain(),
+            collision::resolve_player_collisions,
+            collision::resolve_tether.run_if(resource_exists::<collision::Tether>),
+            (
+                collision::resolve_wall_collisions,
+                projectile::ricochet_projectiles,
+                arena::wrap_positions.run_if(resource_exists::<arena::Wrap>),
+            ).chain(),
+            (arena::teleport_players, arena::bounce_players, hazards::swallow_players).chain(),
+            items::pick_up_items,
+            items::respawn_items,
+            (
+                modes::score_capture_point.run_if(resource_exists::<modes::CapturePoint>),
+                modes::damage_outside_zone.run_if(resource_exists::<modes::SafeZone>),
+                modes::capture_the_flag.run_if(resource_exists::<modes::Ctf>),
+                modes::punish_campers.run_if(resource_exists::<modes::AntiCamping>),
+            ).chain(),
+            projectile::projectile_hits,
+            projectile::beam_hits,
+            (
+                combat::regen_shields,
+                teams::regen_team_energy.run_if(resource_exists::<teams::TeamEnergy>),
+            ).chain(),
+            projectile::expire_projectiles,
+            debug::record_input_history.run_if(resource_exists::<debug::InputHistory>),
+            replay::record_replay.run_if(resource_exists::<replay::ReplayRecorder>),
+            debug::exit_after_max_frames.run_if(resource_exists::<debug::MaxFrames>),
+        ).chain().run_if(net::session_ready))
+}
+
+fn window_plugin(vsync: Vsync) -> WindowPlugin {
+    WindowPlugin {
+        primary_window: Some(Window { present_mode: vsync.present_mode(), ..default() }),
+        ..default()
+    }
+}
+
+// without a primary window there's no winit event loop, so a plain runner drives the app
+fn default_plugins(no_window: bool, vsync: Vsync) -> PluginGroupBuilder {
+    if !no_window {
+        return DefaultPlugins.set(window_plugin(vsync));
+    }
+    DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            ..default()
+        })
+        .disable::<WinitPlugin>()
+        .add(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1. / UPS as f64)))
+}
+
+impl Opt {
+    // a spectator gets every input from the host and only needs to know how many there are
+    fn players_num(&self) -> usize {
+        if self.spectate.is_some() { self.spectate_players } else { self.players.len() + self.demo.unwrap_or(0) }
+    }
+
+    // demo bots have no peers to talk to
+    fn session_kind(&self) -> net::SessionKind {
+        net::session_kind(self.synctest || self.play.is_some(), self.demo.is_some(), self.spectate.is_some(), &self.players, &self.spectators)
+    }
+
+    // a playback seeks by rebuilding the world, rolling back would load what was there before
+    fn check_distance(&self) -> Option<usize> {
+        if self.play.is_some() { Some(0) } else { self.check_distance }
+    }
+}
+
+// everything that can be checked before touching the network or the window
+fn validate(opt: &Opt) -> Result<(), String> {
+    let players_num = opt.players_num();
+    net::check_players(opt.session_kind(), &opt.players, players_num)?;
+    if let Some(p) = opt.players.iter().find(|p| *p != "localhost" && p.parse::<SocketAddr>().is_err()) {
+        return Err(format!("player `{p}` is neither `localhost` nor an ip:port address"));
+    }
+    // a demo or a playback runs as a synctest, remote players would quietly become local ones
+    let offline = if opt.demo.is_some() { Some("--demo") } else { opt.play.as_ref().map(|_| "--play") };
+    if let (Some(flag), Some(p)) = (offline, opt.players.iter().find(|p| *p != "localhost")) {
+        return Err(format!("{flag} runs offline, drop the remote player `{p}` or the {flag}"));
+    }
+    let positive = [
+        ("--projectile-speed", opt.projectile_speed),
+        ("--projectile-radius", opt.projectile_radius),
+        ("--arena-width", opt.arena_width),
+        ("--arena-height", opt.arena_height),
+        ("--velocity-gizmo-scale", opt.velocity_gizmo_scale),
+        ("--tether", opt.tether.unwrap_or(1.)),
+        ("--frame-report", opt.frame_report.unwrap_or(1.)),
+        ("--safe-zone", opt.safe_zone.unwrap_or(1.)),
+    ];
+    if let Some((name, value)) = positive.iter().find(|(_, v)| !(*v > 0. && v.is_finite())) {
+        return Err(format!("{name} must be a positive number, got {value}"));
+    }
+    if !(opt.expected_rtt >= 0. && opt.expected_rtt.is_finite()) {
+        return Err(format!("--expected-rtt can't be negative, got {}", opt.expected_rtt));
+    }
+    if opt.slowmo_factor == 0 {
+        return Err("--slowmo-factor must be at least 1".into());
+    }
+    if opt.spectate.is_some() && opt.spectate_players == 0 {
+        return Err("--spectate-players must be at least 1".into());
+    }
+    let remapped = opt.invert_x.iter().chain(&opt.invert_y).chain(&opt.swap_axes);
+    if let Some(p) = remapped.filter(|&&p| p >= players_num).max() {
+        return Err(format!("can't remap inputs of player {p}, there are only {players_num} players"));
+    }
+    let prediction = net::prediction_settings(opt.no_prediction, opt.input_delay, opt.expected_rtt);
+    if opt.session_kind() == net::SessionKind::SyncTest {
+        prediction.check_distance(opt.check_distance())?;
+    }
+    Ok(())
+}
+
+// prints where the two replays diverge, exiting with an error if they do
+fn diff_replays(a: &PathBuf, b: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let (replay_a, replay_b) = (replay::Replay::load(a)?, replay::Replay::load(b)?);
+    match replay::first_divergence(&replay_a, &replay_b) {
+        Some(report) => {
+            println!("{} and {} diverge at {report}", a.display(), b.display());
+            std::process::exit(1);
+        }
+        None => println!("{} and {} match over {} frames", a.display(), b.display(), replay_a.frames.len()),
+    }
+    Ok(())
+}
+
+// only a networked session binds the local port
+fn start_session(opt: &Opt, prediction: &net::PredictionSettings) -> Result<Session<Config>, Box<dyn Error>> {
     let mut sess_build = SessionBuilder::<Config>::new()
-        .with_num_players(players_num)
+        .with_num_players(opt.players_num())
         // .with_desync_detection_mod(ggrs::DesyncDetection::On {interval: 10})
-        .with_max_prediction_window(12)
-        .unwrap();
+        .with_max_prediction_window(prediction.max_prediction)?
+        .with_input_delay(prediction.input_delay);
 
-    for (i, player_addr) in opt.players.iter().enumerate() {
-        // local player
-        if player_addr == "localhost" {
-            sess_build = sess_build.add_player(PlayerType::Local, i).unwrap();
-        } else {
-            // remote players
-            let remote_addr: SocketAddr = player_addr.parse().unwrap();
-            sess_build = sess_build.add_player(PlayerType::Remote(remote_addr), i).unwrap();
+    let session = if opt.session_kind() == net::SessionKind::SyncTest {
+        let sess = sess_build
+            .with_check_distance(prediction.check_distance(opt.check_distance())?)
+            .start_synctest_session()?;
+        Session::SyncTest(sess)
+    } else if let Some(host) = opt.spectate {
+        let socket = net::LaggySocket::new(
+            UdpNonBlockingSocket::bind_to_port(opt.local_port)?,
+            opt.fake_latency,
+            opt.fake_jitter,
+            opt.seed,
+        );
+        let sess = sess_build
+            .with_max_frames_behind(net::SPECTATOR_MAX_FRAMES_BEHIND)?
+            .with_catchup_speed(net::SPECTATOR_CATCHUP_SPEED)?
+            .start_spectator_session(host, socket);
+        Session::Spectator(sess)
+    } else {
+        for (i, player_addr) in opt.players.iter().enumerate() {
+            // local player
+            if player_addr == "localhost" {
+                sess_build = sess_build.add_player(PlayerType::Local, i)?;
+            } else {
+                // remote players
+                let remote_addr: SocketAddr = player_addr
+                    .parse()
+                    .map_err(|_| format!("`{player_addr}` is neither `localhost` nor an ip:port address"))?;
+                sess_build = sess_build.add_player(PlayerType::Remote(remote_addr), i)?;
+            }
         }
+
+        // spectator handles come after every player
+        for (i, addr) in opt.spectators.iter().enumerate() {
+            sess_build = sess_build.add_player(PlayerType::Spectator(*addr), opt.players_num() + i)?;
+        }
+
+        let socket = net::LaggySocket::new(
+            UdpNonBlockingSocket::bind_to_port(opt.local_port)?,
+            opt.fake_latency,
+            opt.fake_jitter,
+            opt.seed,
+        );
+        Session::P2P(sess_build.start_p2p_session(socket)?)
+    };
+    Ok(session)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opt = Opt::parse();
+    if let [a, b] = opt.diff.as_slice() {
+        return diff_replays(a, b);
+    }
+    if let Err(e) = validate(&opt) {
+        Opt::command().error(ErrorKind::ValueValidation, e).exit();
     }
+    let players_num = opt.players_num();
+    let kind = opt.session_kind();
 
-    let socket = UdpNonBlockingSocket::bind_to_port(opt.local_port).unwrap();
-    let sess = sess_build.start_p2p_session(socket).unwrap();
+    // bots take the handles after the human players
+    let bots = bots::Bots {
+        seed: opt.seed,
+        handles: (opt.players.len()..players_num).collect(),
+        behavior: opt.bot_behavior,
+        wrap: opt.wrap.then_some(Vec2::new(opt.arena_width, opt.arena_height) / 2.),
+    };
 
-    App::new()
-        .add_plugins((
-            DefaultPlugins,
-            Wireframe2dPlugin,
-            GgrsPlugin::<Config>::default()
-            ))
-        .set_rollback_schedule_fps(UPS as usize)
-        .rollback_component_with_clone::<Transform>()
-        .rollback_component_with_copy::<Velocity>()
+    let no_window = opt.no_window;
+    let health_colors = opt.health_colors;
+    let follow_camera = opt.follow_camera;
+    let freeze_tag = opt.freeze_tag;
+    let sumo = opt.sumo;
+    let anti_camping = opt.anti_camping;
+    let handicap = opt.handicap;
+    let team_energy = opt.team_energy.map(teams::TeamEnergy::new);
+    let wrap = opt.wrap;
+    // starts out just around the arena corners
+    let safe_zone = opt.safe_zone.map(|secs| modes::SafeZone {
+        start_radius: Vec2::new(opt.arena_width, opt.arena_height).length() / 2.,
+        shrink_frames: (secs * UPS) as u32,
+    });
+    let offscreen_indicators = opt.offscreen_indicators;
+    let nearest_indicator = opt.nearest_indicator;
+    let velocity_gizmo_scale = opt.debug_velocity.then_some(opt.velocity_gizmo_scale);
+    let log_events = opt.log_events;
+    let particles = !opt.no_particles;
+    let prediction = net::prediction_settings(opt.no_prediction, opt.input_delay, opt.expected_rtt);
+    let input_delay_indicator = opt.show_input_delay.then_some(prediction.input_delay);
+    let list_inputs = opt.list_inputs;
+    let remap = InputRemap::new(players_num, &opt.invert_x, &opt.invert_y, &opt.swap_axes);
+    let frame_report = opt.frame_report.map(debug::FrameReport::new);
+    let input_history = opt.input_history.map(|frames| debug::InputHistory::new(players_num, frames));
+    let map = opt.map.as_deref().map(map::MapSpec::load).transpose()?.unwrap_or_default();
+    let capture_point = map.capture;
+    let ctf = modes::Ctf::from_map(&map);
+    let scored = capture_point.is_some() || ctf.is_some();
+    let recorder = opt.record.clone().map(|path| replay::ReplayRecorder {
+        path,
+        format: opt.record_format,
+        replay: replay::Replay {
+            players: players_num,
+            frames: Vec::new(),
+        },
+    });
+    let ghosts = opt.ghost.as_ref().map(replay::Replay::load).transpose()?.map(replay::GhostReplay);
+    let playback = opt.play.as_ref().map(replay::Replay::load).transpose()?.map(replay::Playback::new);
+    if let Some(recorded) = playback.as_ref().map(|p| p.replay.players).filter(|&n| n != players_num) {
+        let e = format!("the replay has {recorded} players, pass as many with `-p localhost ..`");
+        Opt::command().error(ErrorKind::ValueValidation, e).exit();
+    }
+    // ties the first two players together
+    let tether = opt.tether.filter(|_| players_num >= 2).map(|length| collision::Tether { a: 0, b: 1, length });
+    let wind = Vec2::new(opt.wind_x, opt.wind_y);
+    let wind = (wind != Vec2::ZERO).then_some(hazards::Wind(wind));
+    let gravity_well = opt.gravity.map(|strength| hazards::GravityWell {
+        center: Vec2::ZERO,
+        strength,
+    });
+    let script = opt.script.as_deref().map(script::InputScript::load).transpose()?;
+    let expect = opt
+        .expect
+        .clone()
+        .map(|path| fs::read_to_string(&path).map(|text| (path.clone(), text)).map_err(|e| format!("{}: {e}", path.display())))
+        .transpose()?;
+    let max_frames = opt.max_frames.map(|frames| debug::MaxFrames {
+        frames,
+        dump_state: opt.dump_state,
+        expect,
+    });
+
+    let fit_window = opt.fit_window && kind == net::SessionKind::SyncTest;
+    let ignore_fit_window = opt.fit_window && !fit_window;
+    let session = start_session(&opt, &prediction)?;
+
+    info!("input protocol version {PROTOCOL_VERSION}");
+
+    let mut app = App::new();
+    app.add_plugins((
+        default_plugins(no_window, opt.vsync),
+        Wireframe2dPlugin,
+        GgrsPlugin::<Config>::default()
+    ));
+    add_simulation(&mut app)
+        .init_resource::<KeyBindings>()
+        .insert_resource(remap)
+        .insert_resource(Countdown {
+            frames: opt.countdown * UPS as u32,
+        })
+        .insert_resource(debug::SlowMotion::new(opt.slowmo_factor))
+        .insert_resource(opt.integrator)
+        .insert_resource(opt.weapon)
+        .insert_resource(opt.team_collision)
+        .insert_resource(interpolation::Interpolation { enabled: opt.interpolate })
+        .insert_resource(bots)
+        .insert_resource(arena::Arena {
+            half_size: Vec2::new(opt.arena_width, opt.arena_height) / 2.,
+        })
+        .insert_resource(arena::Portals::from_map(&map))
+        .insert_resource(map)
+        .insert_resource(ProjectileConfig {
+            speed: opt.projectile_speed,
+            radius: opt.projectile_radius,
+            bounces: opt.ricochets,
+        })
         .insert_resource(opt)
-        .insert_resource(Session::P2P(sess))
+        .insert_resource(session)
         .insert_resource(Time::<Fixed>::from_hz(UPS as f64))
-        .add_systems(Startup, setup)
-        .add_systems(GgrsSchedule, (handle_players, velocity_system.after(handle_players)))
-        .add_systems(ReadInputs, (read_local_inputs))
-        .run();
+        .add_systems(Startup, (
+            setup,
+            arena::setup_arena,
+            items::spawn_items,
+            hazards::spawn_floor_zones,
+            projectile::setup_projectile_assets,
+            ui::spawn_input_delay_indicator.run_if(resource_exists::<ui::InputDelayIndicator>),
+            replay::spawn_ghosts.run_if(resource_exists::<replay::GhostReplay>),
+            ui::spawn_countdown_text,
+            ui::spawn_ammo_text,
+            ui::spawn_combo_text,
+            ui::spawn_score_text.run_if(resource_exists::<ui::ShowScore>),
+            ui::spawn_winner_text::<ui::SumoText>.run_if(resource_exists::<modes::Sumo>),
+            ui::spawn_winner_text::<ui::SafeZoneText>.run_if(resource_exists::<modes::SafeZone>),
+            ui::spawn_energy_text.run_if(resource_exists::<teams::TeamEnergy>),
+            ui::spawn_scoreboard,
+            modes::spawn_flag.run_if(resource_exists::<modes::Ctf>),
+        ))
+        .add_systems(First, debug::drive_slow_motion.before(TimeSystem))
+        .add_systems(Update, (
+            debug::toggle_slow_motion,
+            debug::log_checksum,
+            debug::draw_velocity_gizmos.run_if(resource_exists::<debug::VelocityGizmos>),
+            debug::log_sim_events.run_if(resource_exists::<debug::LogEvents>),
+            debug::dump_input_history.run_if(resource_exists::<debug::InputHistory>),
+            debug::report_frame_ratio.run_if(resource_exists::<debug::FrameReport>),
+            input::list_inputs.run_if(resource_exists::<input::ListInputs>),
+            net::refresh_link_quality.run_if(resource_exists::<net::LinkQualities>),
+            net::draw_link_quality.run_if(resource_exists::<net::LinkQualities>),
+            arena::queue_arena_resize.run_if(resource_exists::<arena::ArenaResizes>),
+            arena::resize_wall_meshes.run_if(resource_exists::<arena::ArenaResizes>),
+            interpolation::toggle_interpolation,
+            interpolation::track_interpolation,
+            camera::follow_local_player.run_if(resource_exists::<camera::CameraFollow>),
+            camera::draw_offscreen_indicators
+                .after(camera::follow_local_player)
+                .run_if(resource_exists::<camera::OffscreenIndicators>),
+            camera::draw_nearest_indicator.run_if(resource_exists::<camera::NearestIndicator>),
+        ))
+        .add_systems(Update, (
+            (
+                projectile::add_projectile_visuals,
+                items::add_item_visuals,
+                modes::add_flag_visuals,
+                abilities::add_slow_field_visuals,
+                modes::hide_knocked_out,
+            ),
+            hazards::draw_gravity_well.run_if(resource_exists::<hazards::GravityWell>),
+            hazards::draw_wind.run_if(resource_exists::<hazards::Wind>),
+            hazards::draw_black_holes,
+            collision::draw_tether.run_if(resource_exists::<collision::Tether>),
+            replay::seek_ghosts.run_if(resource_exists::<replay::GhostReplay>),
+            replay::seek_playback.run_if(resource_exists::<replay::Playback>),
+            replay::update_ghosts
+                .after(replay::seek_ghosts)
+                .run_if(resource_exists::<replay::GhostReplay>),
+            arena::draw_portals,
+            arena::draw_bounce_pads,
+            combat::draw_stun_stars,
+            combat::draw_shields,
+            abilities::draw_magnet_auras,
+            abilities::draw_grapples,
+            projectile::draw_beams,
+            (
+                modes::draw_capture_point.run_if(resource_exists::<modes::CapturePoint>),
+                modes::draw_flag_bases.run_if(resource_exists::<modes::Ctf>),
+                modes::draw_frozen_players.run_if(resource_exists::<modes::FreezeTag>),
+                modes::draw_safe_zone.run_if(resource_exists::<modes::SafeZone>),
+                modes::draw_sumo_edge.run_if(resource_exists::<modes::Sumo>),
+            ),
+            (particles::spawn_particles, particles::update_particles)
+                .chain()
+                .run_if(resource_exists::<particles::Particles>),
+            combat::tint_by_health.run_if(resource_exists::<combat::HealthTint>),
+        ))
+        .add_systems(Update, (
+            ui::update_countdown_text,
+            ui::update_ammo_text,
+            ui::update_combo_text,
+            ui::update_score_text.run_if(resource_exists::<ui::ShowScore>),
+            ui::update_sumo_text.run_if(resource_exists::<modes::Sumo>),
+            ui::update_safe_zone_text.run_if(resource_exists::<modes::SafeZone>),
+            ui::update_energy_text.run_if(resource_exists::<teams::TeamEnergy>),
+            ui::update_scoreboard,
+        ))
+        .add_systems(PostUpdate, interpolation::apply_interpolation.after(TransformSystem::TransformPropagate))
+        .add_systems(Last, replay::save_replay_on_exit.run_if(resource_exists::<replay::ReplayRecorder>))
+        .add_systems(ReadInputs, (
+            read_local_inputs,
+            script::read_scripted_inputs.after(read_local_inputs).run_if(resource_exists::<script::InputScript>),
+            replay::read_playback_inputs.after(read_local_inputs).run_if(resource_exists::<replay::Playback>),
+        ));
+
+    if let Some(scale) = velocity_gizmo_scale {
+        app.insert_resource(debug::VelocityGizmos { scale });
+    }
+    if log_events {
+        app.insert_resource(debug::LogEvents);
+    }
+    if particles {
+        app.init_resource::<particles::Particles>();
+    }
+
+    if let Some(history) = input_history {
+        app.insert_resource(history);
+    }
+
+    if kind == net::SessionKind::Spectator {
+        app.add_systems(Startup, ui::spawn_catch_up_text).add_systems(Update, ui::update_catch_up_text);
+    }
+    if kind == net::SessionKind::P2P {
+        app.init_resource::<net::LinkQualities>();
+    }
+    if fit_window {
+        app.init_resource::<arena::ArenaResizes>();
+    }
+    if ignore_fit_window {
+        warn!("--fit-window is ignored with remote players, they can't see our window size");
+    }
+    if let Some(capture_point) = capture_point {
+        app.insert_resource(capture_point);
+    }
+    if let Some(ctf) = ctf {
+        app.insert_resource(ctf);
+    }
+    if scored {
+        app.insert_resource(ui::ShowScore);
+    }
+    if let Some(recorder) = recorder {
+        app.insert_resource(recorder);
+    }
+
+    if let Some(ghosts) = ghosts {
+        app.insert_resource(ghosts).init_resource::<replay::GhostSeek>();
+    }
+    if let Some(playback) = playback {
+        app.insert_resource(playback);
+    }
+
+    if let Some(mode) = list_inputs {
+        app.insert_resource(mode);
+    }
+    if let Some(report) = frame_report {
+        app.insert_resource(report);
+    }
+    if let Some(zone) = safe_zone {
+        app.insert_resource(zone);
+    }
+    if let Some(script) = script {
+        app.insert_resource(script);
+    }
+    if wrap {
+        app.insert_resource(arena::Wrap);
+    }
+    if handicap {
+        app.insert_resource(teams::Handicap);
+    }
+    // the checksum can't do without the resource, so it's only registered along with it
+    if let Some(team_energy) = team_energy {
+        app.insert_resource(team_energy)
+            .rollback_resource_with_copy::<teams::TeamEnergy>()
+            .checksum_resource_with_hash::<teams::TeamEnergy>();
+    }
+    if freeze_tag {
+        app.insert_resource(modes::FreezeTag);
+    }
+    if sumo {
+        app.insert_resource(modes::Sumo);
+    }
+    if anti_camping {
+        app.insert_resource(modes::AntiCamping);
+    }
+    if follow_camera {
+        app.insert_resource(camera::CameraFollow);
+    }
+    if offscreen_indicators {
+        app.insert_resource(camera::OffscreenIndicators);
+    }
+    if nearest_indicator {
+        app.insert_resource(camera::NearestIndicator);
+    }
+    if let Some(tether) = tether {
+        app.insert_resource(tether);
+    }
+    if let Some(wind) = wind {
+        app.insert_resource(wind);
+    }
+    if let Some(well) = gravity_well {
+        app.insert_resource(well);
+    }
+
+    if health_colors {
+        app.insert_resource(combat::HealthTint);
+    }
+
+    if let Some(max_frames) = max_frames {
+        app.insert_resource(max_frames);
+    }
+
+    if let Some(frames) = input_delay_indicator {
+        app.insert_resource(ui::InputDelayIndicator { frames });
+    }
+
+    if let AppExit::Error(code) = app.run() {
+        std::process::exit(code.get().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Harness;
+    use crate::script::InputScript;
+
+    #[test]
+    fn slower_players_cover_less_ground_on_the_same_input() {
+        let mut harness = Harness::new(2, InputScript::parse("1 0 u\n1 1 u").unwrap());
+        let slow = harness.player(1);
+        harness.app.world_mut().get_mut::<Speed>(slow).unwrap().0 = PLAYER_SPEED / 2.;
+        harness.run_to(1);
+        let start: Vec<_> = harness.players().iter().map(|(_, pos, _)| *pos).collect();
+        harness.run_to(31);
+        let players = harness.players();
+        let moved: Vec<_> = players.iter().zip(&start).map(|((_, pos, _), start)| pos.y - start.y).collect();
+        assert!((moved[0] - 30. * PLAYER_SPEED * SPU).abs() < 1e-3, "fast one moved {}", moved[0]);
+        assert!((moved[1] - 30. * PLAYER_SPEED / 2. * SPU).abs() < 1e-3, "slow one moved {}", moved[1]);
+    }
+
+    #[test]
+    fn boots_without_a_primary_window() {
+        let mut app = App::new();
+        app.add_plugins(default_plugins(true, Vsync::On));
+        // what `run` does before its first update
+        app.finish();
+        app.cleanup();
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(app.world_mut().query::<&Window>().iter(app.world()).count(), 0);
+        // nothing closing means nothing to exit for either
+        assert_eq!(app.should_exit(), None);
+    }
+
+    #[test]
+    fn nobody_moves_until_the_countdown_is_over() {
+        let mut harness = Harness::new(1, InputScript::parse("1 0 r").unwrap());
+        harness.app.insert_resource(Countdown { frames: 30 });
+        harness.run_to(30);
+        let (_, frozen_at, vel) = harness.players()[0];
+        assert_eq!((vel.x, vel.y), (0., 0.));
+        let spawn = harness.player(0);
+        assert_eq!(frozen_at, harness.app.world().get::<arena::SpawnPoint>(spawn).unwrap().0);
+        harness.run_to(31);
+        let (_, _, vel) = harness.players()[0];
+        assert_eq!((vel.x, vel.y), (PLAYER_SPEED, 0.));
+    }
+
+    #[test]
+    fn a_lone_local_player_never_binds_the_port() {
+        let taken = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = taken.local_addr().unwrap().port().to_string();
+        let prediction = net::prediction_settings(false, None, 0.);
+
+        let alone = Opt::parse_from(["ptpOnBevy", "-p", "localhost", "--local-port", &port]);
+        assert!(matches!(start_session(&alone, &prediction), Ok(Session::SyncTest(_))));
+
+        // a peer needs the socket, so the taken port gets in the way
+        let with_peer = Opt::parse_from(["ptpOnBevy", "-p", "localhost", "127.0.0.1:9", "--local-port", &port]);
+        assert!(start_session(&with_peer, &prediction).is_err());
+    }
+
+    #[test]
+    fn a_stunned_player_ignores_input_until_the_stun_runs_out() {
+        let mut harness = Harness::new(1, InputScript::parse("1 0 r").unwrap());
+        let player = harness.player(0);
+        harness.app.world_mut().get_mut::<Stun>(player).unwrap().apply(10);
+        for frame in 1..=10 {
+            harness.run_to(frame);
+            let (_, _, vel) = harness.players()[0];
+            assert_eq!((vel.x, vel.y), (0., 0.), "frame {frame}");
+        }
+        harness.run_to(11);
+        let (_, _, vel) = harness.players()[0];
+        assert_eq!((vel.x, vel.y), (PLAYER_SPEED, 0.));
+        assert_eq!(*harness.app.world().get::<Stun>(player).unwrap(), Stun::default());
+    }
+
+    #[test]
+    fn validation_wants_players_and_a_local_one_among_peers() {
+        assert!(validate(&Opt::parse_from(["ptpOnBevy"])).is_err());
+        assert!(validate(&Opt::parse_from(["ptpOnBevy", "-p", "10.0.0.2:7000", "10.0.0.3:7000"])).is_err());
+        assert_eq!(validate(&Opt::parse_from(["ptpOnBevy", "-p", "localhost", "10.0.0.2:7000"])), Ok(()));
+    }
+
+    fn rejected(args: &[&str]) -> String {
+        let opt = Opt::try_parse_from(["ptpOnBevy", "-p", "localhost", "localhost"].iter().chain(args)).unwrap();
+        validate(&opt).unwrap_err()
+    }
+
+    #[test]
+    fn each_bad_option_gets_a_descriptive_error() {
+        let bad_peer = validate(&Opt::parse_from(["ptpOnBevy", "-p", "localhost", "10.0.0.2"])).unwrap_err();
+        assert_eq!(bad_peer, "player `10.0.0.2` is neither `localhost` nor an ip:port address");
+        let remote_demo = validate(&Opt::parse_from(["ptpOnBevy", "--demo", "2", "-p", "localhost", "10.0.0.2:7000"]));
+        assert_eq!(remote_demo.unwrap_err(), "--demo runs offline, drop the remote player `10.0.0.2:7000` or the --demo");
+        assert_eq!(validate(&Opt::parse_from(["ptpOnBevy", "--demo", "2", "-p", "localhost"])), Ok(()));
+        let remote_play = validate(&Opt::parse_from(["ptpOnBevy", "--play", "a.txt", "-p", "localhost", "10.0.0.2:7000"]));
+        assert_eq!(remote_play.unwrap_err(), "--play runs offline, drop the remote player `10.0.0.2:7000` or the --play");
+        assert_eq!(rejected(&["--arena-width", "0"]), "--arena-width must be a positive number, got 0");
+        assert_eq!(rejected(&["--projectile-speed=-3"]), "--projectile-speed must be a positive number, got -3");
+        assert_eq!(rejected(&["--tether", "NaN"]), "--tether must be a positive number, got NaN");
+        assert_eq!(rejected(&["--expected-rtt=-1"]), "--expected-rtt can't be negative, got -1");
+        assert_eq!(rejected(&["--slowmo-factor", "0"]), "--slowmo-factor must be at least 1");
+        assert_eq!(rejected(&["--invert-y", "2"]), "can't remap inputs of player 2, there are only 2 players");
+        assert!(rejected(&["--synctest", "--check-distance", "100"]).starts_with("--check-distance must be smaller"));
+        // malformed values never reach `validate`, clap turns them down with usage
+        assert!(Opt::try_parse_from(["ptpOnBevy", "-p", "localhost", "--colors", "#12"]).is_err());
+        assert!(Opt::try_parse_from(["ptpOnBevy", "-p", "localhost", "--arena-width", "wide"]).is_err());
+    }
+
+    #[test]
+    fn euler_falls_further_behind_semi_implicit_as_the_pull_builds_up() {
+        let run = |integrator| {
+            let mut harness = Harness::new(1, InputScript::default());
+            harness.app.insert_resource(integrator).insert_resource(hazards::GravityWell { center: Vec2::ZERO, strength: 30000. });
+            let player = harness.player(0);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(-300., 0., 0.);
+            (1..=40)
+                .map(|frame| {
+                    harness.run_to(frame);
+                    harness.players()[0].1.x
+                })
+                .collect::<Vec<_>>()
+        };
+        let (euler, semi) = (run(sim::Integrator::Euler), run(sim::Integrator::SemiImplicit));
+        // euler only starts moving with the velocity the first frame worked out
+        assert_eq!(euler[0], -300.);
+        assert!(semi[0] > -300.);
+        let gaps: Vec<_> = semi.iter().zip(&euler).map(|(s, e)| s - e).collect();
+        assert!(gaps.windows(2).all(|w| w[0] < w[1]), "{gaps:?}");
+        // each frame euler is about where semi-implicit was the frame before
+        for (i, e) in euler.iter().enumerate().skip(1) {
+            assert!((e - semi[i - 1]).abs() < 0.01, "frame {}: {e} vs {}", i + 1, semi[i - 1]);
+        }
+    }
+
+    #[test]
+    fn the_window_presents_with_the_chosen_vsync() {
+        let present_mode = |args: &[&str]| {
+            let opt = Opt::try_parse_from(["ptpOnBevy", "-p", "localhost"].iter().chain(args)).unwrap();
+            window_plugin(opt.vsync).primary_window.unwrap().present_mode
+        };
+        assert_eq!(present_mode(&[]), PresentMode::AutoVsync);
+        assert_eq!(present_mode(&["--vsync", "on"]), PresentMode::AutoVsync);
+        assert_eq!(present_mode(&["--vsync", "off"]), PresentMode::AutoNoVsync);
+    }
 }
@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::Path;
+use bevy::prelude::*;
+use crate::arena::{BouncePad, MovingWall};
+use crate::collision::{sides_from_letters, SIDES_ALL};
+use crate::hazards::{BlackHole, MudZone, DEFAULT_ICE_GRIP, DEFAULT_MUD_FACTOR};
+use crate::items::ItemKind;
+use crate::modes::{CapturePoint, FlagBase};
+use crate::teams::{Team, TEAM_COUNT};
+
+const DEFAULT_PORTAL_RADIUS: f32 = 30.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortalPair {
+    pub a: Vec2,
+    pub b: Vec2,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapWall {
+    pub center: Vec2,
+    pub half_size: Vec2,
+    pub sides: u8,
+}
+
+// Static arena layout shared by every peer. One element per line, `#` starts a comment:
+//   portal <ax> <ay> <bx> <by> [radius]
+//   capture <x> <y> <radius>
+//   wall <x> <y> <half_w> <half_h> [solid sides out of `tblr`, all by default]
+//   item <x> <y> <heal|speed|ammo>
+//   mud <x> <y> <half_w> <half_h> [speed factor]
+//   ice <x> <y> <half_w> <half_h> [grip]
+//   blackhole <x> <y> <radius>
+//   flag <x> <y>
+//   base <team> <x> <y> <radius>
+//   pad <x> <y> <radius> <launch vx> <launch vy>
+//   mover <ax> <ay> <bx> <by> <half_w> <half_h> <frames from a to b>
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct MapSpec {
+    pub portals: Vec<PortalPair>,
+    pub capture: Option<CapturePoint>,
+    pub walls: Vec<MapWall>,
+    pub items: Vec<(Vec2, ItemKind)>,
+    pub mud: Vec<MudZone>,
+    pub ice: Vec<MudZone>,
+    pub movers: Vec<MovingWall>,
+    pub black_holes: Vec<BlackHole>,
+    pub pads: Vec<BouncePad>,
+    pub flag: Option<Vec2>,
+    pub bases: Vec<FlagBase>,
+}
+
+fn numbers(args: &[&str], line: usize) -> Result<Vec<f32>, String> {
+    args.iter()
+        .map(|a| a.parse().map_err(|_| format!("line {line}: `{a}` is not a number")))
+        .collect()
+}
+
+impl MapSpec {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = MapSpec::default();
+        for (n, line) in text.lines().enumerate() {
+            let line_no = n + 1;
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((&kind, args)) = words.split_first() else {
+                continue;
+            };
+            // elements may end in a single word option
+            let (v, word) = match args.split_last() {
+                Some((last, rest)) if last.parse::<f32>().is_err() => (numbers(rest, line_no)?, Some(*last)),
+                _ => (numbers(args, line_no)?, None),
+            };
+            match (kind, v.len()) {
+                ("wall", 4) => map.walls.push(MapWall {
+                    center: Vec2::new(v[0], v[1]),
+                    half_size: Vec2::new(v[2], v[3]),
+                    sides: match word {
+                        Some(letters) => sides_from_letters(letters)
+                            .ok_or_else(|| format!("line {line_no}: sides `{letters}` should only use `tblr`"))?,
+                        None => SIDES_ALL,
+                    },
+                }),
+                ("item", 2) => {
+                    let name = word.unwrap_or_default();
+                    let kind = ItemKind::from_name(name)
+                        .ok_or_else(|| format!("line {line_no}: unknown item `{name}`"))?;
+                    map.items.push((Vec2::new(v[0], v[1]), kind));
+                }
+                _ if word.is_some() => return Err(format!("line {line_no}: can't read `{}`", line.trim())),
+                ("mud", 4 | 5) => map.mud.push(MudZone {
+                    center: Vec2::new(v[0], v[1]),
+                    half_size: Vec2::new(v[2], v[3]),
+                    factor: v.get(4).copied().unwrap_or(DEFAULT_MUD_FACTOR),
+                }),
+                ("ice", 4 | 5) => map.ice.push(MudZone {
+                    center: Vec2::new(v[0], v[1]),
+                    half_size: Vec2::new(v[2], v[3]),
+                    factor: v.get(4).copied().unwrap_or(DEFAULT_ICE_GRIP),
+                }),
+                ("mover", 7) => map.movers.push(MovingWall {
+                    a: Vec2::new(v[0], v[1]),
+                    b: Vec2::new(v[2], v[3]),
+                    half_size: Vec2::new(v[4], v[5]),
+                    period: match v[6] {
+                        f if f >= 1. && f.fract() == 0. => f as u32,
+                        f => return Err(format!("line {line_no}: mover period `{f}` should be a whole number of frames")),
+                    },
+                }),
+                ("portal", 4 | 5) => map.portals.push(PortalPair {
+                    a: Vec2::new(v[0], v[1]),
+                    b: Vec2::new(v[2], v[3]),
+                    radius: v.get(4).copied().unwrap_or(DEFAULT_PORTAL_RADIUS),
+                }),
+                ("blackhole", 3) => map.black_holes.push(BlackHole {
+                    center: Vec2::new(v[0], v[1]),
+                    radius: v[2],
+                }),
+                ("pad", 5) => map.pads.push(BouncePad {
+                    center: Vec2::new(v[0], v[1]),
+                    radius: v[2],
+                    launch: Vec2::new(v[3], v[4]),
+                }),
+                ("flag", 2) => map.flag = Some(Vec2::new(v[0], v[1])),
+                ("base", 4) => map.bases.push(FlagBase {
+                    team: match v[0] {
+                        t if t >= 0. && t.fract() == 0. && (t as usize) < TEAM_COUNT => Team(t as usize),
+                        t => return Err(format!("line {line_no}: team `{t}` should be one of 0..{TEAM_COUNT}")),
+                    },
+                    center: Vec2::new(v[1], v[2]),
+                    radius: v[3],
+                }),
+                ("capture", 3) => map.capture = Some(CapturePoint {
+                    center: Vec2::new(v[0], v[1]),
+                    radius: v[2],
+                }),
+                _ => return Err(format!("line {line_no}: can't read `{}`", line.trim())),
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
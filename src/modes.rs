@@ -0,0 +1,664 @@
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use crate::arena::Arena;
+use crate::collision::CollisionLayer;
+use crate::combat::{damage, Health, Shield};
+use crate::map::MapSpec;
+use crate::sim::{Countdown, FrameCounter, SimEvent, SimEvents};
+use crate::teams::{Score, Team, TEAM_COUNT};
+use crate::{Player, RollbackPlayer, Velocity, PLAYER_RADIUS, UPS};
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct CapturePoint {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl CapturePoint {
+    pub fn contains(&self, pos: Vec2) -> bool {
+        pos.distance(self.center) < self.radius
+    }
+}
+
+// the team with strictly the most players inside holds the point, a tie holds nothing
+pub fn capture_holder(point: &CapturePoint, players: impl IntoIterator<Item = (Vec2, Team)>) -> Option<Team> {
+    let mut counts = [0usize; TEAM_COUNT];
+    for (pos, team) in players {
+        if point.contains(pos) {
+            counts[team.0] += 1;
+        }
+    }
+    let most = *counts.iter().max()?;
+    let mut leaders = (0..TEAM_COUNT).filter(|&t| counts[t] == most);
+    match (leaders.next(), leaders.next()) {
+        (Some(team), None) if most > 0 => Some(Team(team)),
+        _ => None,
+    }
+}
+
+pub fn score_capture_point(
+    query: Query<(&Transform, &Team), RollbackPlayer>,
+    point: Res<CapturePoint>,
+    mut score: ResMut<Score>
+) {
+    let players = query.iter().map(|(t, team)| (t.translation.truncate(), *team));
+    if let Some(team) = capture_holder(&point, players) {
+        score.teams[team.0] += 1;
+    }
+}
+
+pub fn draw_capture_point(
+    mut gizmos: Gizmos,
+    query: Query<(&Transform, &Team), RollbackPlayer>,
+    point: Res<CapturePoint>
+) {
+    let players = query.iter().map(|(t, team)| (t.translation.truncate(), *team));
+    let color = capture_holder(&point, players).map_or(Color::WHITE, Team::color);
+    gizmos.circle_2d(point.center, point.radius, color);
+}
+
+// Freeze tag: team 0 tags, everyone else runs. A tagged runner can't move until an
+// unfrozen runner of the same team touches them.
+#[derive(Resource)]
+pub struct FreezeTag;
+
+pub const TAGGER_TEAM: Team = Team(0);
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tagged {
+    pub frozen: bool,
+}
+
+// next frozen state per player, all decided from the previous frame's states
+pub fn freeze_tag_step(players: &[(Vec2, Team, Tagged)]) -> Vec<bool> {
+    let touching = |a: Vec2, b: Vec2| a.distance(b) < 2. * PLAYER_RADIUS;
+    players
+        .iter()
+        .enumerate()
+        .map(|(i, &(pos, team, tagged))| {
+            if team == TAGGER_TEAM {
+                return false;
+            }
+            let others = players.iter().enumerate().filter(|(j, (other, ..))| *j != i && touching(pos, *other));
+            let mut tagger = false;
+            let mut rescuer = false;
+            for (_, (_, other_team, other_tagged)) in others {
+                tagger |= *other_team == TAGGER_TEAM;
+                rescuer |= *other_team == team && !other_tagged.frozen;
+            }
+            // a tagger standing next to the rescue wins
+            tagger || (tagged.frozen && !rescuer)
+        })
+        .collect()
+}
+
+pub fn freeze_tag(mut query: Query<(&Transform, &Team, &mut Tagged, &mut Velocity, &Player), RollbackPlayer>) {
+    let mut players: Vec<_> = query.iter_mut().collect();
+    players.sort_by_key(|(.., player)| player.id);
+    let states: Vec<_> = players.iter().map(|(t, team, tagged, ..)| (t.translation.truncate(), **team, **tagged)).collect();
+    for ((_, _, tagged, vel, _), frozen) in players.iter_mut().zip(freeze_tag_step(&states)) {
+        tagged.frozen = frozen;
+        if frozen {
+            vel.x = 0.;
+            vel.y = 0.;
+        }
+    }
+}
+
+pub fn draw_frozen_players(mut gizmos: Gizmos, query: Query<(&Transform, &Tagged), With<Player>>) {
+    for (transform, tagged) in query.iter() {
+        if tagged.frozen {
+            gizmos.circle_2d(transform.translation.truncate(), PLAYER_RADIUS + 4., Color::srgb(0.6, 0.9, 1.));
+        }
+    }
+}
+
+// Sumo: no walls, a player whose center leaves the arena is out for the rest of the
+// match and the last one standing wins.
+#[derive(Resource)]
+pub struct Sumo;
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KnockedOut {
+    pub out: bool,
+}
+
+pub fn off_edge(pos: Vec2, half_size: Vec2) -> bool {
+    pos.x.abs() > half_size.x || pos.y.abs() > half_size.y
+}
+
+// `players` is (handle, knocked out), there's only a winner once everyone else is out
+pub fn sumo_winner(players: &[(usize, KnockedOut)]) -> Option<usize> {
+    let mut standing = players.iter().filter(|(_, k)| !k.out);
+    match (standing.next(), standing.next()) {
+        (Some((id, _)), None) if players.len() > 1 => Some(*id),
+        _ => None,
+    }
+}
+
+type Contestant = (
+    &'static Transform,
+    &'static mut KnockedOut,
+    &'static mut Velocity,
+    &'static mut CollisionLayer,
+);
+
+// runs right after the input is applied, so players that are out can't move any more,
+// and they stop colliding with anything
+pub fn knock_off_edge(mut query: Query<Contestant, RollbackPlayer>, arena: Res<Arena>) {
+    for (transform, mut knocked_out, mut vel, mut layer) in query.iter_mut() {
+        knocked_out.out |= off_edge(transform.translation.truncate(), arena.half_size);
+        if knocked_out.out {
+            vel.x = 0.;
+            vel.y = 0.;
+            *layer = CollisionLayer::new(0, 0);
+        }
+    }
+}
+
+pub fn draw_sumo_edge(mut gizmos: Gizmos, arena: Res<Arena>) {
+    gizmos.rect_2d(Vec2::ZERO, 0., arena.half_size * 2., Color::srgb(1., 0.35, 0.2));
+}
+
+type NewlyKnockedOut = (With<Player>, Changed<KnockedOut>);
+
+pub fn hide_knocked_out(mut query: Query<(&KnockedOut, &mut Visibility), NewlyKnockedOut>) {
+    for (knocked_out, mut visibility) in query.iter_mut() {
+        *visibility = if knocked_out.out { Visibility::Hidden } else { Visibility::Inherited };
+    }
+}
+
+pub const SAFE_ZONE_MIN_RADIUS: f32 = 60.;
+// players outside the zone take damage every this many frames
+const ZONE_DAMAGE_INTERVAL: u32 = 30;
+const ZONE_DAMAGE: i32 = 2;
+
+// Battle royale circle around the arena center, shrinking from `start_radius` down to
+// `SAFE_ZONE_MIN_RADIUS` over `shrink_frames`. Once it's done shrinking it's sudden death
+// and the damage outside goes up by one more every second.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct SafeZone {
+    pub start_radius: f32,
+    pub shrink_frames: u32,
+}
+
+impl SafeZone {
+    pub fn radius_at(&self, frame: u32) -> f32 {
+        let t = frame.min(self.shrink_frames) as f32 / self.shrink_frames.max(1) as f32;
+        self.start_radius + (SAFE_ZONE_MIN_RADIUS - self.start_radius) * t
+    }
+
+    // damage dealt on `frame` to a player outside, zero between intervals
+    pub fn damage_at(&self, frame: u32) -> i32 {
+        if frame == 0 || !frame.is_multiple_of(ZONE_DAMAGE_INTERVAL) {
+            return 0;
+        }
+        let sudden_death = frame.saturating_sub(self.shrink_frames) / UPS as u32;
+        ZONE_DAMAGE + sudden_death as i32
+    }
+}
+
+// `players` is (handle, hp), the last one with any health left wins
+pub fn zone_survivor(players: &[(usize, i32)]) -> Option<usize> {
+    let mut standing = players.iter().filter(|(_, hp)| *hp > 0);
+    match (standing.next(), standing.next()) {
+        (Some((id, _)), None) if players.len() > 1 => Some(*id),
+        _ => None,
+    }
+}
+
+pub fn damage_outside_zone(
+    mut query: Query<(&Transform, &mut Health, &mut Shield), RollbackPlayer>,
+    zone: Res<SafeZone>,
+    frame: Res<FrameCounter>,
+    countdown: Res<Countdown>
+) {
+    let amount = zone.damage_at(frame.0);
+    if amount == 0 || countdown.active(frame.0) {
+        return;
+    }
+    let radius = zone.radius_at(frame.0);
+    for (transform, mut health, mut shield) in query.iter_mut() {
+        if transform.translation.truncate().length() > radius {
+            damage(&mut health, &mut shield, amount, frame.0);
+        }
+    }
+}
+
+pub fn draw_safe_zone(mut gizmos: Gizmos, zone: Res<SafeZone>, frame: Res<FrameCounter>) {
+    let color = if frame.0 >= zone.shrink_frames { Color::srgb(1., 0.2, 0.2) } else { Color::srgb(1., 0.6, 0.2) };
+    gizmos.circle_2d(Vec2::ZERO, zone.radius_at(frame.0), color);
+}
+
+// slower than this counts as standing still
+const IDLE_SPEED: f32 = 20.;
+pub const CAMPING_FRAMES: u32 = 5 * UPS as u32;
+const CAMPING_DAMAGE_INTERVAL: u32 = 30;
+const CAMPING_DAMAGE: i32 = 3;
+
+// Anti-camping: a player standing still for `CAMPING_FRAMES` starts taking damage every
+// `CAMPING_DAMAGE_INTERVAL` frames until they move again.
+#[derive(Resource)]
+pub struct AntiCamping;
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Idle {
+    pub frames: u32,
+}
+
+pub fn camping_damage(idle_frames: u32) -> i32 {
+    match idle_frames.checked_sub(CAMPING_FRAMES) {
+        Some(over) if over.is_multiple_of(CAMPING_DAMAGE_INTERVAL) => CAMPING_DAMAGE,
+        _ => 0,
+    }
+}
+
+pub fn punish_campers(
+    mut query: Query<(&Velocity, &mut Idle, &mut Health, &mut Shield), RollbackPlayer>,
+    frame: Res<FrameCounter>,
+    countdown: Res<Countdown>
+) {
+    for (vel, mut idle, mut health, mut shield) in query.iter_mut() {
+        // everyone is held in place during the countdown, that doesn't count as camping
+        if countdown.active(frame.0) || Vec2::new(vel.x, vel.y).length() >= IDLE_SPEED {
+            idle.frames = 0;
+        } else {
+            idle.frames = idle.frames.saturating_add(1);
+        }
+        let amount = camping_damage(idle.frames);
+        if amount > 0 {
+            damage(&mut health, &mut shield, amount, frame.0);
+        }
+    }
+}
+
+const FLAG_RADIUS: f32 = 10.;
+pub const CARRIER_SPEED_FACTOR: f32 = 0.7;
+// score is counted in frames, a capture is worth as much as holding a point for 10 seconds
+pub const FLAG_CAPTURE_POINTS: u32 = 10 * UPS as u32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlagBase {
+    pub team: Team,
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+// Capture the flag, on maps with a flag and at least one base. Whoever touches the
+// flag carries it, slowed down, and scores by bringing it into their team's base.
+#[derive(Resource, Clone, Debug)]
+pub struct Ctf {
+    pub home: Vec2,
+    pub bases: Vec<FlagBase>,
+}
+
+impl Ctf {
+    pub fn from_map(map: &MapSpec) -> Option<Self> {
+        let home = map.flag?;
+        (!map.bases.is_empty()).then(|| Ctf { home, bases: map.bases.clone() })
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Flag {
+    pub carrier: Option<usize>,
+}
+
+pub fn spawn_flag(mut commands: Commands, ctf: Res<Ctf>) {
+    commands.spawn((Flag::default(), Transform::from_translation(ctf.home.extend(1.)))).add_rollback();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagEvent {
+    PickedUp(usize),
+    Dropped,
+    Captured(Team),
+}
+
+// `players` is (id, position, team, hp) sorted by id, so a tied pickup goes to the lowest
+// id. A carrier that was `relocated` this frame, by a portal or a respawn, lets go of it.
+pub fn flag_step(
+    flag: &Flag,
+    flag_pos: Vec2,
+    players: &[(usize, Vec2, Team, i32)],
+    bases: &[FlagBase],
+    relocated: &[usize]
+) -> Option<FlagEvent> {
+    match flag.carrier {
+        Some(id) => {
+            let Some(&(_, pos, team, hp)) = players.iter().find(|p| p.0 == id) else {
+                return Some(FlagEvent::Dropped);
+            };
+            if hp <= 0 || relocated.contains(&id) {
+                return Some(FlagEvent::Dropped);
+            }
+            let home = bases.iter().any(|b| b.team == team && pos.distance(b.center) < b.radius);
+            home.then_some(FlagEvent::Captured(team))
+        }
+        None => players
+            .iter()
+            .find(|&&(_, pos, _, hp)| hp > 0 && pos.distance(flag_pos) < FLAG_RADIUS + PLAYER_RADIUS)
+            .map(|&(id, ..)| FlagEvent::PickedUp(id)),
+    }
+}
+
+type FlagEntity = (With<Rollback>, Without<Player>);
+
+pub fn capture_the_flag(
+    mut flags: Query<(&mut Transform, &mut Flag), FlagEntity>,
+    players: Query<(&Transform, &Player, &Team, &Health), RollbackPlayer>,
+    ctf: Res<Ctf>,
+    mut score: ResMut<Score>,
+    events: Res<SimEvents>
+) {
+    let relocated: Vec<_> = events
+        .0
+        .iter()
+        .filter_map(|e| match e {
+            SimEvent::Relocated { player, .. } => Some(*player),
+            _ => None,
+        })
+        .collect();
+    let mut players: Vec<_> = players
+        .iter()
+        .map(|(t, player, team, health)| (player.id, t.translation.truncate(), *team, health.hp))
+        .collect();
+    players.sort_by_key(|p| p.0);
+
+    for (mut transform, mut flag) in flags.iter_mut() {
+        match flag_step(&flag, transform.translation.truncate(), &players, &ctf.bases, &relocated) {
+            Some(FlagEvent::PickedUp(id)) => flag.carrier = Some(id),
+            Some(FlagEvent::Dropped) => flag.carrier = None,
+            Some(FlagEvent::Captured(team)) => {
+                score.teams[team.0] += FLAG_CAPTURE_POINTS;
+                flag.carrier = None;
+                transform.translation = ctf.home.extend(transform.translation.z);
+            }
+            None => {}
+        }
+        if let Some(&(_, pos, ..)) = flag.carrier.and_then(|id| players.iter().find(|p| p.0 == id)) {
+            transform.translation = pos.extend(transform.translation.z);
+        }
+    }
+}
+
+pub fn slow_flag_carriers(
+    mut players: Query<(&Player, &mut Velocity), RollbackPlayer>,
+    flags: Query<&Flag, With<Rollback>>
+) {
+    for (player, mut vel) in players.iter_mut() {
+        if flags.iter().any(|f| f.carrier == Some(player.id)) {
+            vel.x *= CARRIER_SPEED_FACTOR;
+            vel.y *= CARRIER_SPEED_FACTOR;
+        }
+    }
+}
+
+pub fn add_flag_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<Entity, (With<Flag>, Without<Mesh2dHandle>)>
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(RegularPolygon::new(FLAG_RADIUS, 3))),
+            materials.add(Color::WHITE),
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+pub fn draw_flag_bases(mut gizmos: Gizmos, ctf: Res<Ctf>) {
+    for base in &ctf.bases {
+        gizmos.circle_2d(base.center, base.radius, base.team.color());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+    use crate::harness::{spawn_rollback, Harness};
+    use crate::projectile::Projectile;
+    use crate::script::InputScript;
+    use crate::PLAYER_SPEED;
+
+    const BASES: [FlagBase; 2] = [
+        FlagBase { team: Team(0), center: Vec2::new(-300., 0.), radius: 40. },
+        FlagBase { team: Team(1), center: Vec2::new(300., 0.), radius: 40. },
+    ];
+
+    #[test]
+    fn safe_zone_shrinks_to_the_minimum_and_stays() {
+        let zone = SafeZone { start_radius: 460., shrink_frames: 400 };
+        assert_eq!(zone.radius_at(0), 460.);
+        assert_eq!(zone.radius_at(200), 260.);
+        assert_eq!(zone.radius_at(400), SAFE_ZONE_MIN_RADIUS);
+        assert_eq!(zone.radius_at(10_000), SAFE_ZONE_MIN_RADIUS);
+    }
+
+    #[test]
+    fn safe_zone_damage_ticks_and_grows_in_sudden_death() {
+        let zone = SafeZone { start_radius: 460., shrink_frames: 300 };
+        assert_eq!(zone.damage_at(0), 0);
+        assert_eq!(zone.damage_at(31), 0);
+        assert_eq!(zone.damage_at(300), ZONE_DAMAGE);
+        assert_eq!(zone.damage_at(300 + 2 * UPS as u32), ZONE_DAMAGE + 2);
+    }
+
+    #[test]
+    fn only_players_outside_the_zone_get_hurt_once_the_countdown_is_over() {
+        let mut world = World::new();
+        world.insert_resource(SafeZone { start_radius: 460., shrink_frames: 300 });
+        world.insert_resource(Countdown { frames: 30 });
+        let mut player = |id, x| spawn_rollback(&mut world, (Player { id }, Transform::from_xyz(x, 0., 0.), Health::default(), Shield::default()));
+        let (inside, outside) = (player(0, 100.), player(1, 500.));
+        let lost = |world: &World, e| {
+            let (health, shield) = (world.get::<Health>(e).unwrap(), world.get::<Shield>(e).unwrap());
+            Health::default().hp - health.hp + Shield::default().hp - shield.hp
+        };
+
+        // a damage frame, but still counting down
+        world.insert_resource(FrameCounter(30));
+        world.run_system_once(damage_outside_zone);
+        assert_eq!((lost(&world, inside), lost(&world, outside)), (0, 0));
+        // between damage frames
+        world.insert_resource(FrameCounter(59));
+        world.run_system_once(damage_outside_zone);
+        assert_eq!((lost(&world, inside), lost(&world, outside)), (0, 0));
+        world.insert_resource(FrameCounter(60));
+        world.run_system_once(damage_outside_zone);
+        assert_eq!((lost(&world, inside), lost(&world, outside)), (0, ZONE_DAMAGE));
+    }
+
+    #[test]
+    fn last_one_standing_wins() {
+        assert_eq!(zone_survivor(&[(0, 5), (1, 0), (2, 0)]), Some(0));
+        assert_eq!(zone_survivor(&[(0, 5), (1, 3)]), None);
+        assert_eq!(zone_survivor(&[(0, 0), (1, 0)]), None);
+        assert_eq!(zone_survivor(&[(0, 5)]), None);
+        let out = KnockedOut { out: true };
+        assert_eq!(sumo_winner(&[(0, out), (1, KnockedOut::default())]), Some(1));
+        assert_eq!(sumo_winner(&[(0, KnockedOut::default())]), None);
+    }
+
+    #[test]
+    fn campers_are_hit_once_per_interval_after_the_grace_period() {
+        assert_eq!(camping_damage(0), 0);
+        assert_eq!(camping_damage(CAMPING_FRAMES - 1), 0);
+        assert_eq!(camping_damage(CAMPING_FRAMES), CAMPING_DAMAGE);
+        assert_eq!(camping_damage(CAMPING_FRAMES + 1), 0);
+        assert_eq!(camping_damage(CAMPING_FRAMES + CAMPING_DAMAGE_INTERVAL), CAMPING_DAMAGE);
+    }
+
+    #[test]
+    fn only_the_player_standing_still_is_punished_for_camping() {
+        let mut world = World::new();
+        world.insert_resource(Countdown { frames: 0 });
+        // no shields, so every hit shows up in health
+        let shield = Shield { hp: 0, last_hit: 0 };
+        let mut player = |id, vel: Velocity| {
+            spawn_rollback(&mut world, (Player { id }, vel, Idle::default(), Health::default(), shield))
+        };
+        let (camper, mover) = (player(0, Velocity { x: 0., y: 0. }), player(1, Velocity { x: PLAYER_SPEED, y: 0. }));
+        let state = |world: &World, e| (world.get::<Idle>(e).unwrap().frames, world.get::<Health>(e).unwrap().hp);
+        let full = Health::default().hp;
+        let run_to = |world: &mut World, to: u32| {
+            for frame in world.resource::<FrameCounter>().0 + 1..=to {
+                world.insert_resource(FrameCounter(frame));
+                world.run_system_once(punish_campers);
+            }
+        };
+        world.insert_resource(FrameCounter(0));
+
+        run_to(&mut world, CAMPING_FRAMES - 1);
+        assert_eq!(state(&world, camper), (CAMPING_FRAMES - 1, full));
+        run_to(&mut world, CAMPING_FRAMES);
+        assert_eq!(state(&world, camper), (CAMPING_FRAMES, full - CAMPING_DAMAGE));
+        run_to(&mut world, CAMPING_FRAMES + CAMPING_DAMAGE_INTERVAL - 1);
+        assert_eq!(state(&world, camper).1, full - CAMPING_DAMAGE);
+        run_to(&mut world, CAMPING_FRAMES + CAMPING_DAMAGE_INTERVAL);
+        assert_eq!(state(&world, camper), (CAMPING_FRAMES + CAMPING_DAMAGE_INTERVAL, full - 2 * CAMPING_DAMAGE));
+        assert_eq!(state(&world, mover), (0, full));
+
+        // moving again starts the count over
+        world.get_mut::<Velocity>(camper).unwrap().x = PLAYER_SPEED;
+        run_to(&mut world, CAMPING_FRAMES + CAMPING_DAMAGE_INTERVAL + 1);
+        assert_eq!(state(&world, camper), (0, full - 2 * CAMPING_DAMAGE));
+    }
+
+    #[test]
+    fn a_slowed_carrier_brings_the_flag_home_and_scores() {
+        let mut harness = Harness::new(1, InputScript::parse("1 0 l").unwrap());
+        harness.app.insert_resource(Ctf { home: Vec2::ZERO, bases: BASES.to_vec() });
+        let player = harness.player(0);
+        harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(100., 0., 0.);
+        let flag = spawn_rollback(harness.app.world_mut(), (Flag::default(), Transform::from_xyz(0., 0., 1.)));
+        let carrier = |harness: &Harness| harness.app.world().get::<Flag>(flag).unwrap().carrier;
+
+        while carrier(&harness).is_none() {
+            assert!(harness.frame() < 60, "never picked the flag up");
+            harness.run_to(harness.frame() + 1);
+        }
+        assert_eq!(carrier(&harness), Some(0));
+        harness.run_to(harness.frame() + 1);
+        assert_eq!(harness.players()[0].2.x, -PLAYER_SPEED * CARRIER_SPEED_FACTOR);
+
+        while carrier(&harness).is_some() {
+            assert_eq!(harness.app.world().resource::<Score>().teams, [0, 0]);
+            assert!(harness.frame() < 300, "never made it home");
+            harness.run_to(harness.frame() + 1);
+        }
+        assert_eq!(harness.app.world().resource::<Score>().teams, [FLAG_CAPTURE_POINTS, 0]);
+        // the flag goes back home, away from the base the carrier is standing in
+        assert_eq!(harness.app.world().get::<Transform>(flag).unwrap().translation.truncate(), Vec2::ZERO);
+        assert!(harness.players()[0].1.distance(BASES[0].center) < BASES[0].radius);
+    }
+
+    #[test]
+    fn lowest_id_picks_up_a_free_flag() {
+        let players = [(0, Vec2::new(5., 0.), Team(0), 10), (1, Vec2::new(-5., 0.), Team(1), 10)];
+        assert_eq!(flag_step(&Flag::default(), Vec2::ZERO, &players, &BASES, &[]), Some(FlagEvent::PickedUp(0)));
+        let knocked_out = [(0, Vec2::ZERO, Team(0), 0)];
+        assert_eq!(flag_step(&Flag::default(), Vec2::ZERO, &knocked_out, &BASES, &[]), None);
+    }
+
+    #[test]
+    fn carrier_captures_at_its_own_base() {
+        let carried = Flag { carrier: Some(1) };
+        let home = [(1, Vec2::new(300., 10.), Team(1), 10)];
+        assert_eq!(flag_step(&carried, Vec2::ZERO, &home, &BASES, &[]), Some(FlagEvent::Captured(Team(1))));
+        let enemy_base = [(1, Vec2::new(-300., 10.), Team(1), 10)];
+        assert_eq!(flag_step(&carried, Vec2::ZERO, &enemy_base, &BASES, &[]), None);
+    }
+
+    #[test]
+    fn carrier_drops_the_flag_when_relocated_or_down() {
+        let carried = Flag { carrier: Some(1) };
+        let players = [(0, Vec2::ZERO, Team(0), 10), (1, Vec2::new(300., 0.), Team(1), 10)];
+        assert_eq!(flag_step(&carried, Vec2::ZERO, &players, &BASES, &[1]), Some(FlagEvent::Dropped));
+        assert_eq!(flag_step(&carried, Vec2::ZERO, &players[..1], &BASES, &[0]), Some(FlagEvent::Dropped));
+        let down = [(1, Vec2::ZERO, Team(1), 0)];
+        assert_eq!(flag_step(&carried, Vec2::ZERO, &down, &BASES, &[]), Some(FlagEvent::Dropped));
+    }
+
+    #[test]
+    fn the_team_with_more_players_inside_scores_the_point() {
+        let point = CapturePoint { center: Vec2::ZERO, radius: 50. };
+        let mut world = World::new();
+        world.insert_resource(point);
+        world.init_resource::<Score>();
+        let mut player = |id, x, team| spawn_rollback(&mut world, (Player { id }, Transform::from_xyz(x, 0., 0.), Team(team)));
+        player(0, 10., 0);
+        player(1, -10., 1);
+        player(2, 20., 1);
+        // outside, doesn't count for team 0
+        player(3, 60., 0);
+        player(4, 200., 0);
+        world.run_system_once(score_capture_point);
+        world.run_system_once(score_capture_point);
+        assert_eq!(world.resource::<Score>().teams[..2], [0, 2]);
+
+        // even numbers inside hold nothing
+        let tied = [(Vec2::ZERO, Team(0)), (Vec2::X, Team(1)), (Vec2::new(90., 0.), Team(1))];
+        assert_eq!(capture_holder(&point, tied), None);
+        assert_eq!(capture_holder(&point, []), None);
+    }
+
+    #[test]
+    fn a_tag_freezes_a_runner_and_a_teammate_frees_them() {
+        let mut world = World::new();
+        let mut spawn = |id, x, team| {
+            let bundle = (Player { id }, Transform::from_xyz(x, 0., 0.), Team(team), Tagged::default(), Velocity { x: 50., y: 0. });
+            spawn_rollback(&mut world, bundle)
+        };
+        let tagger = spawn(0, 0., TAGGER_TEAM.0);
+        let runner = spawn(1, PLAYER_RADIUS, 1);
+        let teammate = spawn(2, 500., 1);
+        let frozen = |world: &World| (world.get::<Tagged>(runner).unwrap().frozen, world.get::<Velocity>(runner).unwrap().x);
+
+        world.run_system_once(freeze_tag);
+        assert_eq!(frozen(&world), (true, 0.));
+        assert!(!world.get::<Tagged>(tagger).unwrap().frozen);
+
+        // stays frozen once the tagger is gone, until the teammate comes over
+        world.get_mut::<Transform>(tagger).unwrap().translation.x = -500.;
+        world.get_mut::<Velocity>(runner).unwrap().x = 50.;
+        world.run_system_once(freeze_tag);
+        assert_eq!(frozen(&world), (true, 0.));
+        world.get_mut::<Transform>(teammate).unwrap().translation.x = 2. * PLAYER_RADIUS;
+        world.get_mut::<Velocity>(runner).unwrap().x = 50.;
+        world.run_system_once(freeze_tag);
+        assert_eq!(frozen(&world), (false, 50.));
+    }
+
+    #[test]
+    fn pushed_over_the_edge_is_out_and_the_pusher_wins() {
+        // player 0 runs into player 1 and lets go before following it over, then player 1
+        // tries shooting back from off the edge
+        let mut harness = Harness::new(2, InputScript::parse("1 0 r\n31 0 -\n61 1 f\n62 1 -").unwrap());
+        harness.app.insert_resource(Sumo);
+        let half = harness.app.world().resource::<Arena>().half_size;
+        for (id, x) in [(0, half.x - 100.), (1, half.x - 45.)] {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(x, 0., 0.);
+        }
+        harness.run_to(60);
+
+        let mut query = harness.app.world_mut().query::<(&Player, &KnockedOut, &Transform)>();
+        let mut players: Vec<_> = query.iter(harness.app.world()).map(|(p, k, t)| (p.id, *k, t.translation.x)).collect();
+        players.sort_by_key(|(id, ..)| *id);
+        assert!(!players[0].1.out, "the pusher stops short of the edge at {}", players[0].2);
+        assert!(players[1].1.out);
+        assert!(players[1].2 > half.x);
+        let standings: Vec<_> = players.iter().map(|(id, k, _)| (*id, *k)).collect();
+        assert_eq!(sumo_winner(&standings), Some(0));
+
+        harness.run_to(70);
+        let world = harness.app.world_mut();
+        assert_eq!(world.query::<&Projectile>().iter(world).count(), 0);
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use bevy::prelude::*;
+use bevy_ggrs::ggrs::{InputStatus, Message, NonBlockingSocket, SessionState};
+use bevy_ggrs::{PlayerInputs, Session};
+use crate::input::{InputPacked, PROTOCOL_VERSION};
+use crate::rng::{mix, SimRng};
+use crate::{Config, Player, PLAYER_RADIUS, UPS};
+
+const MAX_PREDICTION: usize = 12;
+// a spectator further behind the host than this runs several frames per step until it's back
+pub const SPECTATOR_MAX_FRAMES_BEHIND: usize = 10;
+// two frames per step, so catching up speeds the game up instead of skipping ahead
+pub const SPECTATOR_CATCHUP_SPEED: usize = 2;
+// ggrs 0.10 rejects a window of 0, so one frame is as close to pure delay-based as it gets
+const MIN_PREDICTION: usize = 1;
+
+// gameplay shouldn't see inputs from before every peer has synchronized
+pub fn session_ready(session: Option<Res<Session<Config>>>) -> bool {
+    match session.as_deref() {
+        Some(Session::SyncTest(_)) => true,
+        Some(Session::P2P(s)) => s.current_state() == SessionState::Running,
+        Some(Session::Spectator(s)) => s.current_state() == SessionState::Running,
+        None => false,
+    }
+}
+
+// Predicted inputs are guesses and disconnected ones are zeroed, only confirmed
+// ones tell what version a peer runs. ggrs also fills the input delay frames at the
+// start with zeroed inputs marked confirmed, so version 0 never counts, no build sends
+// it. Returns the first (handle, version) that differs.
+pub fn protocol_mismatch(inputs: &[(InputPacked, InputStatus)]) -> Option<(usize, u8)> {
+    inputs
+        .iter()
+        .enumerate()
+        .find(|(_, (input, status))| {
+            *status == InputStatus::Confirmed && input.version != 0 && input.version != PROTOCOL_VERSION
+        })
+        .map(|(handle, (input, _))| (handle, input.version))
+}
+
+pub fn reject_protocol_mismatch(
+    inputs: Res<PlayerInputs<Config>>,
+    mut exit: EventWriter<AppExit>,
+    mut done: Local<bool>
+) {
+    if *done {
+        return;
+    }
+    if let Some((handle, version)) = protocol_mismatch(&inputs) {
+        *done = true;
+        error!(
+            "player {handle} runs input protocol version {version}, we run {PROTOCOL_VERSION}; \
+             every peer needs a build with the same version"
+        );
+        exit.send(AppExit::error());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PredictionSettings {
+    pub max_prediction: usize,
+    pub input_delay: usize,
+}
+
+const DEFAULT_CHECK_DISTANCE: usize = 2;
+
+impl PredictionSettings {
+    // A synctest rolls back this many frames every frame, it has to fit in the prediction
+    // window. Left unset it shrinks to fit, so `--no-prediction` works without it.
+    pub fn check_distance(&self, requested: Option<usize>) -> Result<usize, String> {
+        match requested {
+            None => Ok(DEFAULT_CHECK_DISTANCE.min(self.max_prediction - 1)),
+            Some(distance) if distance < self.max_prediction => Ok(distance),
+            Some(_) => Err(format!("--check-distance must be smaller than the prediction window ({})", self.max_prediction)),
+        }
+    }
+}
+
+// Without prediction, remote inputs have to arrive before their frame runs, so the
+// delay has to cover the one-way trip. That trades input latency for (almost) no
+// rollbacks, which only pays off on low-latency LANs.
+pub fn prediction_settings(no_prediction: bool, input_delay: Option<usize>, rtt_ms: f32) -> PredictionSettings {
+    if !no_prediction {
+        return PredictionSettings {
+            max_prediction: MAX_PREDICTION,
+            input_delay: input_delay.unwrap_or(0),
+        };
+    }
+    let frame_ms = 1000. / UPS;
+    PredictionSettings {
+        max_prediction: MIN_PREDICTION,
+        input_delay: input_delay.unwrap_or((rtt_ms / 2. / frame_ms).ceil() as usize),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionKind {
+    SyncTest,
+    P2P,
+    Spectator,
+}
+
+// a lone local player has nobody to talk to, unless someone is watching
+pub fn session_kind(synctest: bool, demo: bool, spectate: bool, players: &[String], spectators: &[SocketAddr]) -> SessionKind {
+    let single_local = players.len() == 1 && players[0] == "localhost" && spectators.is_empty();
+    if spectate {
+        SessionKind::Spectator
+    } else if synctest || demo || single_local {
+        SessionKind::SyncTest
+    } else {
+        SessionKind::P2P
+    }
+}
+
+pub fn check_players(kind: SessionKind, players: &[String], players_num: usize) -> Result<(), String> {
+    if players_num == 0 {
+        return Err("no players given, pass them with `-p localhost <peer addr>..`, or use `--demo N`".into());
+    }
+    if kind == SessionKind::P2P && !players.iter().any(|p| p == "localhost") {
+        return Err("none of the players is `localhost`, list our own slot as `localhost` among the peers \
+                    (e.g. `-p localhost 10.0.0.2:7000`) or watch with `--spectate HOST`"
+            .into());
+    }
+    Ok(())
+}
+
+// ggrs is running catch-up steps
+pub fn catching_up(frames_behind: usize) -> bool {
+    frames_behind > SPECTATOR_MAX_FRAMES_BEHIND
+}
+
+const LINK_QUALITY_REFRESH_SECS: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkQuality {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl LinkQuality {
+    pub fn color(self) -> Color {
+        match self {
+            LinkQuality::Good => Color::srgb(0.2, 0.9, 0.2),
+            LinkQuality::Fair => Color::srgb(0.9, 0.8, 0.1),
+            LinkQuality::Poor => Color::srgb(0.9, 0.2, 0.2),
+        }
+    }
+
+    fn bars(self) -> usize {
+        match self {
+            LinkQuality::Good => 3,
+            LinkQuality::Fair => 2,
+            LinkQuality::Poor => 1,
+        }
+    }
+}
+
+// being many frames behind means rollbacks every frame, even when the ping looks fine
+pub fn link_quality(ping_ms: u128, frames_behind: i32) -> LinkQuality {
+    let behind = frames_behind.unsigned_abs();
+    if ping_ms <= 80 && behind <= 2 {
+        LinkQuality::Good
+    } else if ping_ms <= 150 && behind <= 5 {
+        LinkQuality::Fair
+    } else {
+        LinkQuality::Poor
+    }
+}
+
+// per remote handle, `None` until ggrs has stats for it
+#[derive(Resource)]
+pub struct LinkQualities {
+    timer: Timer,
+    players: Vec<(usize, Option<LinkQuality>)>,
+}
+
+impl Default for LinkQualities {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(LINK_QUALITY_REFRESH_SECS, TimerMode::Repeating),
+            players: Vec::new(),
+        }
+    }
+}
+
+pub fn refresh_link_quality(time: Res<Time>, session: Res<Session<Config>>, mut qualities: ResMut<LinkQualities>) {
+    if !qualities.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Session::P2P(s) = &*session else {
+        return;
+    };
+    qualities.players = s
+        .remote_player_handles()
+        .into_iter()
+        .map(|handle| {
+            let quality = s
+                .network_stats(handle)
+                .ok()
+                .map(|stats| link_quality(stats.ping, stats.remote_frames_behind));
+            (handle, quality)
+        })
+        .collect();
+}
+
+pub fn draw_link_quality(
+    mut gizmos: Gizmos,
+    qualities: Res<LinkQualities>,
+    query: Query<(&Transform, &Player)>
+) {
+    for (transform, player) in query.iter() {
+        let Some((_, quality)) = qualities.players.iter().find(|(handle, _)| *handle == player.id) else {
+            continue;
+        };
+        let (bars, color) = quality.map_or((0, Color::srgb(0.5, 0.5, 0.5)), |q| (q.bars(), q.color()));
+        let base = transform.translation.truncate() + Vec2::new(PLAYER_RADIUS, PLAYER_RADIUS);
+        for i in 0..3 {
+            let x = base.x + i as f32 * 5.;
+            let top = base.y + 4. + i as f32 * 4.;
+            let bar_color = if i < bars { color } else { Color::srgba(0.5, 0.5, 0.5, 0.4) };
+            gizmos.line_2d(Vec2::new(x, base.y), Vec2::new(x, top), bar_color);
+        }
+    }
+}
+
+// Items held back until their release time, equal times come out in the order they went in.
+#[derive(Debug)]
+pub struct DelayQueue<T> {
+    pending: VecDeque<(Duration, T)>,
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        DelayQueue { pending: VecDeque::new() }
+    }
+}
+
+impl<T> DelayQueue<T> {
+    pub fn push(&mut self, release_at: Duration, item: T) {
+        let at = self.pending.partition_point(|(t, _)| *t <= release_at);
+        self.pending.insert(at, (release_at, item));
+    }
+
+    pub fn release(&mut self, now: Duration) -> Vec<T> {
+        let due = self.pending.partition_point(|(t, _)| *t <= now);
+        self.pending.drain(..due).map(|(_, item)| item).collect()
+    }
+}
+
+// Testing aid wrapping the real socket: every outgoing packet waits `latency` give or take
+// up to `jitter` before it's actually sent, so jittered packets can overtake each other.
+// Only the transport is affected, the game sees the same inputs a slow link would bring.
+pub struct LaggySocket<S> {
+    inner: S,
+    start: Instant,
+    latency: Duration,
+    jitter: Duration,
+    rng: SimRng,
+    outgoing: DelayQueue<(SocketAddr, Message)>,
+}
+
+impl<S> LaggySocket<S> {
+    pub fn new(inner: S, latency_ms: u64, jitter_ms: u64, seed: u64) -> Self {
+        LaggySocket {
+            inner,
+            start: Instant::now(),
+            latency: Duration::from_millis(latency_ms),
+            jitter: Duration::from_millis(jitter_ms),
+            rng: SimRng::new(mix(seed, LAG_SEED_KEY)),
+            outgoing: DelayQueue::default(),
+        }
+    }
+
+    fn delay(&mut self) -> Duration {
+        let spread = self.jitter.mul_f32(self.rng.next_f32() * 2.);
+        (self.latency + spread).saturating_sub(self.jitter)
+    }
+}
+
+const LAG_SEED_KEY: u64 = 0x1a66_50c7;
+
+impl<S: NonBlockingSocket<SocketAddr>> NonBlockingSocket<SocketAddr> for LaggySocket<S> {
+    fn send_to(&mut self, msg: &Message, addr: &SocketAddr) {
+        if self.latency.is_zero() && self.jitter.is_zero() {
+            return self.inner.send_to(msg, addr);
+        }
+        let release_at = self.start.elapsed() + self.delay();
+        self.outgoing.push(release_at, (*addr, msg.clone()));
+    }
+
+    // ggrs polls this every update, so it's also where delayed packets go out
+    fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        for (addr, msg) in self.outgoing.release(self.start.elapsed()) {
+            self.inner.send_to(&msg, &addr);
+        }
+        self.inner.receive_all_messages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_ggrs::ggrs::PlayerType;
+    use bevy_ggrs::prelude::SessionBuilder;
+    use super::*;
+
+    #[test]
+    fn matching_versions_pass() {
+        let inputs = [(InputPacked::new(0b1010), InputStatus::Confirmed), (InputPacked::new(0), InputStatus::Predicted)];
+        assert_eq!(protocol_mismatch(&inputs), None);
+    }
+
+    #[test]
+    fn confirmed_other_version_is_rejected() {
+        let old = InputPacked { wasd: 1, version: PROTOCOL_VERSION - 1 };
+        let inputs = [(InputPacked::new(0), InputStatus::Confirmed), (old, InputStatus::Confirmed)];
+        assert_eq!(protocol_mismatch(&inputs), Some((1, PROTOCOL_VERSION - 1)));
+    }
+
+    #[test]
+    fn predicted_and_disconnected_inputs_are_ignored() {
+        let old = InputPacked { wasd: 1, version: PROTOCOL_VERSION + 1 };
+        let inputs = [(old, InputStatus::Predicted), (old, InputStatus::Disconnected)];
+        assert_eq!(protocol_mismatch(&inputs), None);
+    }
+
+    #[test]
+    fn check_distance_fits_the_window() {
+        let predicting = prediction_settings(false, None, 50.);
+        assert_eq!(predicting.check_distance(None), Ok(DEFAULT_CHECK_DISTANCE));
+        assert_eq!(predicting.check_distance(Some(5)), Ok(5));
+        assert!(predicting.check_distance(Some(MAX_PREDICTION)).is_err());
+
+        let delay_only = prediction_settings(true, None, 50.);
+        assert_eq!(delay_only.check_distance(None), Ok(0));
+        assert!(delay_only.check_distance(Some(1)).is_err());
+    }
+
+    #[test]
+    fn no_prediction_delay_covers_one_way_trip() {
+        // 100ms there and back is 50ms one way, three frames at 60 fps
+        let settings = prediction_settings(true, None, 100.);
+        assert_eq!(settings, PredictionSettings { max_prediction: MIN_PREDICTION, input_delay: 3 });
+        assert_eq!(prediction_settings(true, Some(1), 100.).input_delay, 1);
+        assert_eq!(prediction_settings(false, None, 100.).input_delay, 0);
+    }
+
+    #[test]
+    fn input_delay_fill_is_ignored() {
+        // what ggrs hands out for the first frames with an input delay
+        let fill = InputPacked { wasd: 0, version: 0 };
+        let inputs = [(fill, InputStatus::Confirmed), (fill, InputStatus::Confirmed)];
+        assert_eq!(protocol_mismatch(&inputs), None);
+    }
+
+    // a peer that never answers, so a session with it never gets past synchronizing
+    struct Silent;
+
+    impl NonBlockingSocket<SocketAddr> for Silent {
+        fn send_to(&mut self, _msg: &Message, _addr: &SocketAddr) {}
+
+        fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn simulation_waits_for_the_session_to_synchronize() {
+        let mut world = World::new();
+        assert!(!world.run_system_once(session_ready));
+
+        let p2p = SessionBuilder::<Config>::new()
+            .with_num_players(2)
+            .add_player(PlayerType::Local, 0)
+            .unwrap()
+            .add_player(PlayerType::Remote("127.0.0.1:7001".parse().unwrap()), 1)
+            .unwrap()
+            .start_p2p_session(Silent)
+            .unwrap();
+        world.insert_resource(Session::P2P(p2p));
+        assert!(!world.run_system_once(session_ready));
+
+        let synctest = SessionBuilder::<Config>::new().with_num_players(1).start_synctest_session().unwrap();
+        world.insert_resource(Session::SyncTest(synctest));
+        assert!(world.run_system_once(session_ready));
+    }
+
+    #[test]
+    fn link_quality_thresholds() {
+        assert_eq!(link_quality(0, 0), LinkQuality::Good);
+        assert_eq!(link_quality(80, -2), LinkQuality::Good);
+        assert_eq!(link_quality(81, 0), LinkQuality::Fair);
+        assert_eq!(link_quality(20, 3), LinkQuality::Fair);
+        assert_eq!(link_quality(150, -5), LinkQuality::Fair);
+        assert_eq!(link_quality(151, 0), LinkQuality::Poor);
+        // a fine ping doesn't help when rolling back all the time
+        assert_eq!(link_quality(10, 6), LinkQuality::Poor);
+        assert!(LinkQuality::Good.bars() > LinkQuality::Fair.bars());
+        assert!(LinkQuality::Fair.bars() > LinkQuality::Poor.bars());
+    }
+
+    #[test]
+    fn empty_or_remote_only_player_lists_are_rejected() {
+        let peer = vec!["10.0.0.2:7000".to_string()];
+        let local = vec!["localhost".to_string(), "10.0.0.2:7000".to_string()];
+        assert!(check_players(SessionKind::P2P, &[], 0).unwrap_err().contains("no players given"));
+        assert!(check_players(SessionKind::P2P, &peer, 1).unwrap_err().contains("none of the players is `localhost`"));
+        assert_eq!(check_players(SessionKind::P2P, &local, 2), Ok(()));
+        // spectators and bots don't need a local slot
+        assert_eq!(check_players(SessionKind::Spectator, &[], 2), Ok(()));
+        assert_eq!(check_players(SessionKind::SyncTest, &[], 3), Ok(()));
+    }
+
+    #[test]
+    fn delayed_packets_come_out_once_their_time_is_up_in_release_order() {
+        let ms = Duration::from_millis;
+        let mut queue = DelayQueue::default();
+        queue.push(ms(50), "a");
+        // jittered past `a`, and one due at the very same time keeps its push order
+        queue.push(ms(30), "b");
+        queue.push(ms(50), "c");
+        queue.push(ms(80), "d");
+        assert!(queue.release(ms(29)).is_empty());
+        assert_eq!(queue.release(ms(30)), vec!["b"]);
+        assert!(queue.release(ms(49)).is_empty());
+        assert_eq!(queue.release(ms(60)), vec!["a", "c"]);
+        assert_eq!(queue.release(ms(1000)), vec!["d"]);
+        assert!(queue.release(ms(2000)).is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::prelude::*;
+use crate::collision::CollisionKind;
+use crate::rng::SimRng;
+use crate::sim::{FrameCounter, SimEvent, SimEvents};
+use crate::{Player, Velocity, PLAYER_RADIUS};
+
+const SPARKS_PER_HIT: usize = 8;
+const SPARK_SPEED: f32 = 160.;
+const SPARK_LIFETIME: f32 = 0.35;
+const DUST_SPEED: f32 = 30.;
+const DUST_LIFETIME: f32 = 0.5;
+// players slower than this don't kick up dust
+const DUST_MIN_SPEED: f32 = 100.;
+// per player per frame
+const DUST_CHANCE: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub color: Color,
+}
+
+// Render-only. Particles live in this resource rather than as entities, so nothing here
+// can ever be rolled back or checksummed, and the rng is seeded from the clock since
+// peers don't have to agree on any of it.
+#[derive(Resource)]
+pub struct Particles {
+    rng: SimRng,
+    pub list: Vec<Particle>,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Particles { rng: SimRng::new(seed), list: Vec::new() }
+    }
+}
+
+impl Particles {
+    fn random_dir(&mut self) -> Vec2 {
+        Vec2::from_angle(self.rng.next_f32() * std::f32::consts::TAU)
+    }
+
+    pub fn burst(&mut self, at: Vec2, count: usize, speed: f32, lifetime: f32, color: Color) {
+        for _ in 0..count {
+            let vel = self.random_dir() * speed * (0.5 + self.rng.next_f32() / 2.);
+            self.list.push(Particle { pos: at, vel, age: 0., lifetime, color });
+        }
+    }
+}
+
+// `SimEvents` holds the latest simulated frame, so each frame's events only spawn once
+pub fn spawn_particles(
+    mut particles: ResMut<Particles>,
+    events: Res<SimEvents>,
+    players: Query<(&Transform, &Velocity), With<Player>>,
+    frame: Res<FrameCounter>,
+    mut spawned: Local<Option<u32>>
+) {
+    if *spawned == Some(frame.0) {
+        return;
+    }
+    *spawned = Some(frame.0);
+    for event in &events.0 {
+        match *event {
+            SimEvent::Collision { kind: CollisionKind::Projectile, point, .. } => {
+                particles.burst(point, SPARKS_PER_HIT, SPARK_SPEED, SPARK_LIFETIME, Color::srgb(1., 0.8, 0.3))
+            }
+            SimEvent::Collision { point, .. } => {
+                particles.burst(point, 2, DUST_SPEED, DUST_LIFETIME, Color::srgba(0.8, 0.75, 0.65, 0.6))
+            }
+            SimEvent::Relocated { to, .. } => {
+                particles.burst(to, SPARKS_PER_HIT, DUST_SPEED, DUST_LIFETIME, Color::srgba(0.6, 0.9, 1., 0.7))
+            }
+        }
+    }
+    for (transform, vel) in players.iter() {
+        let vel = Vec2::new(vel.x, vel.y);
+        if vel.length() > DUST_MIN_SPEED && particles.rng.next_f32() < DUST_CHANCE {
+            let feet = transform.translation.truncate() - vel.normalize() * PLAYER_RADIUS;
+            particles.burst(feet, 1, DUST_SPEED, DUST_LIFETIME, Color::srgba(0.8, 0.75, 0.65, 0.6));
+        }
+    }
+}
+
+pub fn update_particles(mut gizmos: Gizmos, mut particles: ResMut<Particles>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    particles.list.retain_mut(|p| {
+        p.age += dt;
+        p.pos += p.vel * dt;
+        p.age < p.lifetime
+    });
+    for p in &particles.list {
+        let fade = 1. - p.age / p.lifetime;
+        gizmos.circle_2d(p.pos, 1. + 2. * fade, p.color.with_alpha(p.color.alpha() * fade));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_ggrs::Rollback;
+    use super::*;
+    use crate::harness::spawn_rollback;
+
+    #[test]
+    fn a_hit_throws_sparks_without_spawning_any_entities() {
+        let mut world = World::new();
+        world.init_resource::<Particles>();
+        world.insert_resource(FrameCounter(1));
+        let a = spawn_rollback(&mut world, (Player { id: 0 }, Transform::default(), Velocity { x: 0., y: 0. }));
+        let b = spawn_rollback(&mut world, (Transform::from_xyz(20., 0., 0.), Velocity { x: 300., y: 0. }));
+        let hit = SimEvent::Collision { kind: CollisionKind::Projectile, a, b, point: Vec2::new(10., 0.) };
+        world.insert_resource(SimEvents(vec![hit]));
+        let entities = world.entities().len();
+        let rollback = world.query_filtered::<(), With<Rollback>>().iter(&world).count();
+
+        world.run_system_once(spawn_particles);
+        let particles = &world.resource::<Particles>().list;
+        assert_eq!(particles.len(), SPARKS_PER_HIT);
+        assert!(particles.iter().all(|p| p.pos == Vec2::new(10., 0.)));
+        assert_eq!(world.entities().len(), entities);
+        assert_eq!(world.query_filtered::<(), With<Rollback>>().iter(&world).count(), rollback);
+
+        // without events a standing player kicks up nothing
+        world.insert_resource(SimEvents::default());
+        world.insert_resource(FrameCounter(2));
+        world.run_system_once(spawn_particles);
+        assert_eq!(world.resource::<Particles>().list.len(), SPARKS_PER_HIT);
+    }
+}
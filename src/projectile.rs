@@ -0,0 +1,603 @@
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+use clap::ValueEnum;
+use crate::arena::{distance, nearest_player, offset, Arena, Wrap};
+use crate::collision::{circle_box_contact, reflect, CollisionKind, CollisionLayer, Wall};
+use crate::combat::{damage, Combo, Health, Shield, Stun, STUN_FRAMES};
+use crate::sim::{ready, Cooldown, Countdown, FrameCounter, SimEvent, SimEvents};
+use crate::input::InputLayout;
+use crate::modes::KnockedOut;
+use crate::{Config, Facing, LastInput, Player, Velocity, PLAYER_RADIUS, SPU};
+
+// frames a projectile lives before it's removed
+const PROJECTILE_LIFETIME: u32 = 120;
+const PROJECTILE_DAMAGE: i32 = 10;
+// a full charge doubles speed and damage
+pub const MAX_CHARGE_FRAMES: u32 = 60;
+pub const FIRE_COOLDOWN: u32 = 15;
+pub const BEAM_LENGTH: f32 = 500.;
+const BEAM_WIDTH: f32 = 4.;
+// frames a fired beam stays on screen, it only hits on the first one
+const BEAM_VISIBLE_FRAMES: u32 = 8;
+pub const MAX_AMMO: u32 = 6;
+pub const RELOAD_FRAMES: u32 = 90;
+// radians a homing projectile can turn per frame, slow enough to be dodged
+pub const HOMING_TURN_RATE: f32 = 0.04;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Shot;
+
+type Shooter = (
+    &'static Transform,
+    &'static Player,
+    &'static Facing,
+    &'static mut LastInput,
+    &'static mut Charge,
+    &'static mut Cooldown<Shot>,
+    &'static mut Ammo,
+    &'static KnockedOut,
+);
+
+type Target = (
+    Entity,
+    &'static Transform,
+    &'static Player,
+    &'static CollisionLayer,
+    &'static mut Health,
+    &'static mut Shield,
+    &'static mut Stun,
+    &'static mut Combo,
+);
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ProjectileConfig {
+    pub speed: f32,
+    pub radius: f32,
+    // wall bounces a new projectile gets, it's gone on the first wall it hits after those
+    pub bounces: u32,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Projectile {
+    pub owner: usize,
+    pub radius: f32,
+    pub damage: i32,
+    pub spawned_at: u32,
+    pub bounces: u32,
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Weapon {
+    #[default]
+    Projectile,
+    Beam,
+    Homing,
+}
+
+// projectile turning toward the nearest player other than its owner every frame
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Homing;
+
+// `vel` turned toward `toward` by at most `max_turn` radians, keeping its speed
+pub fn steer(vel: Vec2, toward: Vec2, max_turn: f32) -> Vec2 {
+    let angle = vel.angle_between(toward);
+    if !angle.is_finite() || vel == Vec2::ZERO || toward == Vec2::ZERO {
+        return vel;
+    }
+    Vec2::from_angle(angle.clamp(-max_turn, max_turn)).rotate(vel)
+}
+
+type HomingProjectile = (&'static Transform, &'static Projectile, &'static mut Velocity);
+
+pub fn steer_homing(
+    mut projectiles: Query<HomingProjectile, (With<Homing>, With<Rollback>)>,
+    players: Query<(Entity, &Transform, &Player), With<Rollback>>,
+    arena: Res<Arena>,
+    wrap: Option<Res<Wrap>>
+) {
+    let wrap = wrap.map(|_| arena.half_size);
+    for (transform, projectile, mut vel) in projectiles.iter_mut() {
+        let pos = transform.translation.truncate();
+        let targets: Vec<_> = players
+            .iter()
+            .filter(|(_, _, p)| p.id != projectile.owner)
+            .map(|(e, t, p)| (e, p.id, t.translation.truncate()))
+            .collect();
+        let Some(target) = nearest_player(pos, &targets, wrap) else {
+            continue;
+        };
+        let Some((_, _, target_pos)) = targets.iter().find(|(e, _, _)| *e == target) else {
+            continue;
+        };
+        let steered = steer(Vec2::new(vel.x, vel.y), offset(pos, *target_pos, wrap), HOMING_TURN_RATE);
+        *vel = Velocity { x: steered.x, y: steered.y };
+    }
+}
+
+// Hitscan shot, hitting every player along it on the frame it's fired. `dir` is the
+// shooter's facing, which only takes the eight input directions.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Beam {
+    pub owner: usize,
+    pub origin: Vec2,
+    pub dir: Vec2,
+    pub damage: i32,
+    pub fired_at: u32,
+}
+
+impl Beam {
+    pub fn hits(&self, pos: Vec2) -> bool {
+        let along = (pos - self.origin).dot(self.dir);
+        let across = (pos - self.origin).perp_dot(self.dir).abs();
+        (0. ..=BEAM_LENGTH).contains(&along) && across < PLAYER_RADIUS + BEAM_WIDTH / 2.
+    }
+}
+
+// frames the fire key has been held for
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Charge {
+    pub frames: u32,
+}
+
+// the first frame held is the press itself, so a tap fires an uncharged shot
+fn charged_frames(held: u32) -> u32 {
+    held.saturating_sub(1).min(MAX_CHARGE_FRAMES)
+}
+
+pub fn charge_scale(held: u32) -> f32 {
+    1. + charged_frames(held) as f32 / MAX_CHARGE_FRAMES as f32
+}
+
+// integer math so damage can't drift between peers
+pub fn charged_damage(held: u32) -> i32 {
+    PROJECTILE_DAMAGE + PROJECTILE_DAMAGE * charged_frames(held) as i32 / MAX_CHARGE_FRAMES as i32
+}
+
+// shots left, an empty magazine reloads by itself after `RELOAD_FRAMES`
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Ammo {
+    pub rounds: u32,
+    pub reload: u32,
+}
+
+impl Default for Ammo {
+    fn default() -> Self {
+        Self { rounds: MAX_AMMO, reload: 0 }
+    }
+}
+
+impl Ammo {
+    // uses up a round, false if there was none
+    pub fn take(&mut self) -> bool {
+        if self.rounds == 0 {
+            return false;
+        }
+        self.rounds -= 1;
+        if self.rounds == 0 {
+            self.reload = RELOAD_FRAMES;
+        }
+        true
+    }
+
+    pub fn tick(&mut self) {
+        if self.reload > 0 {
+            self.reload -= 1;
+            if self.reload == 0 {
+                self.rounds = MAX_AMMO;
+            }
+        }
+    }
+
+    pub fn refill(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[derive(Resource)]
+pub struct ProjectileAssets {
+    mesh: Mesh2dHandle,
+    material: Handle<ColorMaterial>,
+}
+
+pub fn projectile_bundle(
+    config: &ProjectileConfig,
+    owner: usize,
+    pos: Vec2,
+    dir: Vec2,
+    charge: u32,
+    frame: u32
+) -> (Projectile, Velocity, Transform, CollisionLayer) {
+    let vel = dir * config.speed * charge_scale(charge);
+    (
+        Projectile {
+            owner,
+            radius: config.radius,
+            damage: charged_damage(charge),
+            spawned_at: frame,
+            bounces: config.bounces,
+        },
+        Velocity { x: vel.x, y: vel.y },
+        Transform::from_translation(pos.extend(1.)),
+        CollisionLayer::projectile(),
+    )
+}
+
+pub fn fire_projectiles(
+    mut commands: Commands,
+    mut query: Query<Shooter, With<Rollback>>,
+    inputs: Res<PlayerInputs<Config>>,
+    config: Res<ProjectileConfig>,
+    countdown: Res<Countdown>,
+    weapon: Res<Weapon>,
+    frame: Res<FrameCounter>
+) {
+    let frozen = countdown.active(frame.0);
+    let mut shooters: Vec<_> = query.iter_mut().collect();
+    // spawn order has to match between peers
+    shooters.sort_by_key(|(_, player, ..)| player.id);
+
+    // holding fire charges the shot, letting go of it fires
+    for (transform, player, facing, last, charge, cooldown, ammo, knocked_out) in shooters.iter_mut() {
+        ammo.tick();
+        let wasd = inputs[player.id].0.wasd;
+        let pressed = InputLayout::Fire.is_set(wasd);
+        let was_pressed = InputLayout::Fire.is_set(last.wasd);
+        last.wasd = wasd;
+
+        // knocked out players are off the arena for good, they don't get to shoot back in
+        if frozen || knocked_out.out {
+            charge.frames = 0;
+        } else if pressed {
+            charge.frames = (charge.frames + 1).min(MAX_CHARGE_FRAMES + 1);
+        } else if was_pressed {
+            if ready(cooldown, frame.0) && ammo.take() {
+                cooldown.trigger(frame.0);
+                let pos = transform.translation.truncate() + facing.0 * (PLAYER_RADIUS + config.radius);
+                match *weapon {
+                    Weapon::Projectile => commands
+                        .spawn(projectile_bundle(&config, player.id, pos, facing.0, charge.frames, frame.0))
+                        .add_rollback(),
+                    Weapon::Homing => commands
+                        .spawn((projectile_bundle(&config, player.id, pos, facing.0, charge.frames, frame.0), Homing))
+                        .add_rollback(),
+                    Weapon::Beam => commands
+                        .spawn(Beam {
+                            owner: player.id,
+                            origin: transform.translation.truncate(),
+                            dir: facing.0,
+                            damage: charged_damage(charge.frames),
+                            fired_at: frame.0,
+                        })
+                        .add_rollback(),
+                };
+            }
+            charge.frames = 0;
+        }
+    }
+}
+
+pub fn projectile_hits(
+    mut commands: Commands,
+    projectiles: Query<(Entity, &Transform, &Projectile, &CollisionLayer), With<Rollback>>,
+    mut players: Query<Target, With<Rollback>>,
+    frame: Res<FrameCounter>,
+    wrap: Option<Res<Wrap>>,
+    arena: Res<Arena>,
+    mut events: ResMut<SimEvents>
+) {
+    let wrap = wrap.map(|_| arena.half_size);
+    let mut targets: Vec<_> = players.iter_mut().collect();
+    targets.sort_by_key(|(_, _, player, ..)| player.id);
+    let mut projectiles: Vec<_> = projectiles.iter().collect();
+    // a player only fires once per cooldown, so owner and spawn frame tell projectiles apart
+    projectiles.sort_by_key(|(_, _, projectile, _)| (projectile.owner, projectile.spawned_at));
+
+    for (entity, transform, projectile, layer) in projectiles {
+        let pos = transform.translation.truncate();
+        let hit = targets.iter().position(|(_, target, player, target_layer, ..)| {
+            player.id != projectile.owner
+                && layer.interacts(target_layer)
+                && distance(pos, target.translation.truncate(), wrap) < projectile.radius + PLAYER_RADIUS
+        });
+        if let Some(i) = hit {
+            let (target, target_transform, _, _, health, shield, stun, _) = &mut targets[i];
+            let to_projectile = offset(target_transform.translation.truncate(), pos, wrap);
+            events.0.push(SimEvent::Collision {
+                kind: CollisionKind::Projectile,
+                a: *target,
+                b: entity,
+                point: target_transform.translation.truncate() + to_projectile.normalize_or_zero() * PLAYER_RADIUS,
+            });
+            damage(health, shield, projectile.damage, frame.0);
+            stun.apply(STUN_FRAMES);
+            if let Some((.., combo)) = targets.iter_mut().find(|(_, _, player, ..)| player.id == projectile.owner) {
+                combo.hit(frame.0);
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn beam_hits(
+    mut commands: Commands,
+    beams: Query<(Entity, &Beam), With<Rollback>>,
+    mut players: Query<Target, With<Rollback>>,
+    frame: Res<FrameCounter>
+) {
+    let mut targets: Vec<_> = players.iter_mut().collect();
+    targets.sort_by_key(|(_, _, player, ..)| player.id);
+    let mut beams: Vec<_> = beams.iter().collect();
+    beams.sort_by_key(|(_, beam)| (beam.owner, beam.fired_at));
+
+    for (entity, beam) in beams {
+        if frame.elapsed_since(beam.fired_at, BEAM_VISIBLE_FRAMES) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if beam.fired_at != frame.0 {
+            continue;
+        }
+        let mut hits = 0;
+        for (_, transform, player, _, health, shield, stun, _) in targets.iter_mut() {
+            if player.id != beam.owner && beam.hits(transform.translation.truncate()) {
+                damage(health, shield, beam.damage, frame.0);
+                stun.apply(STUN_FRAMES);
+                hits += 1;
+            }
+        }
+        if let Some((.., combo)) = targets.iter_mut().find(|(_, _, player, ..)| player.id == beam.owner) {
+            for _ in 0..hits {
+                combo.hit(frame.0);
+            }
+        }
+    }
+}
+
+pub fn draw_beams(mut gizmos: Gizmos, query: Query<&Beam>) {
+    for beam in query.iter() {
+        gizmos.line_2d(beam.origin, beam.origin + beam.dir * BEAM_LENGTH, Color::srgb(1., 0.3, 0.9));
+    }
+}
+
+// every shot stops at walls, bouncing off while it has bounces left and gone after that
+pub fn ricochet_projectiles(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &mut Transform, &mut Velocity, &mut Projectile, &CollisionLayer), With<Rollback>>,
+    walls: Query<(&Transform, &Wall, &CollisionLayer), Without<Projectile>>
+) {
+    let mut walls: Vec<_> = walls.iter().collect();
+    walls.sort_by(|(a, _, _), (b, _, _)| {
+        a.translation.x.total_cmp(&b.translation.x).then(a.translation.y.total_cmp(&b.translation.y))
+    });
+
+    for (entity, mut transform, mut vel, mut projectile, layer) in projectiles.iter_mut() {
+        let v = Vec2::new(vel.x, vel.y);
+        let center = transform.translation.truncate();
+        let prev_center = center - v * SPU;
+        let hit = walls.iter().find_map(|(wall_transform, wall, wall_layer)| {
+            let box_center = wall_transform.translation.truncate();
+            let (normal, depth) = circle_box_contact(center, projectile.radius, box_center, wall.half_size)?;
+            (layer.interacts(wall_layer) && wall.blocks(normal, prev_center, box_center, projectile.radius))
+                .then_some((normal, depth))
+        });
+        let Some((normal, depth)) = hit else {
+            continue;
+        };
+        if projectile.bounces == 0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        projectile.bounces -= 1;
+        transform.translation += (normal * depth).extend(0.);
+        let bounced = reflect(v, normal);
+        vel.x = bounced.x;
+        vel.y = bounced.y;
+    }
+}
+
+pub fn expire_projectiles(
+    mut commands: Commands,
+    query: Query<(Entity, &Projectile), With<Rollback>>,
+    frame: Res<FrameCounter>
+) {
+    for (entity, projectile) in query.iter() {
+        if frame.elapsed_since(projectile.spawned_at, PROJECTILE_LIFETIME) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn setup_projectile_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<ProjectileConfig>
+) {
+    commands.insert_resource(ProjectileAssets {
+        mesh: Mesh2dHandle(meshes.add(Circle::new(config.radius))),
+        material: materials.add(Color::WHITE),
+    });
+}
+
+// rollback only restores registered components, so visuals are (re)attached here
+pub fn add_projectile_visuals(
+    mut commands: Commands,
+    query: Query<Entity, (With<Projectile>, Without<Mesh2dHandle>)>,
+    assets: Res<ProjectileAssets>
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            assets.mesh.clone(),
+            assets.material.clone(),
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+    use crate::combat::SHIELD_MAX_HP;
+    use crate::harness::{spawn_rollback, Harness};
+    use crate::script::InputScript;
+
+    fn shot(world: &mut World, x: f32, bounces: u32) -> Entity {
+        spawn_rollback(world, (
+            Projectile { owner: 0, radius: 6., damage: 1, spawned_at: 0, bounces },
+            Velocity { x: 400., y: 50. },
+            Transform::from_xyz(x, 0., 1.),
+            CollisionLayer::projectile(),
+        ))
+    }
+
+    #[test]
+    fn projectile_reflects_off_a_wall_and_uses_up_a_bounce() {
+        let mut world = World::new();
+        world.spawn((Transform::from_xyz(100., 0., 0.), Wall::solid(Vec2::new(10., 100.)), CollisionLayer::wall()));
+        let bouncing = shot(&mut world, 86., 1);
+        let spent = shot(&mut world, 86., 0);
+        world.run_system_once(ricochet_projectiles);
+
+        let vel = world.get::<Velocity>(bouncing).unwrap();
+        assert_eq!((vel.x, vel.y), (-400., 50.));
+        assert_eq!(world.get::<Projectile>(bouncing).unwrap().bounces, 0);
+        // pushed back out of the wall
+        assert_eq!(world.get::<Transform>(bouncing).unwrap().translation.x, 84.);
+        assert!(world.get_entity(spent).is_none());
+    }
+
+    #[test]
+    fn fired_projectile_gets_the_configured_speed_and_radius() {
+        // a one frame tap: charging on frame 1, fired on release at frame 2
+        let mut harness = Harness::new(1, InputScript::parse("1 0 f\n2 0 -").unwrap());
+        harness.app.insert_resource(ProjectileConfig { speed: 300., radius: 9., bounces: 0 });
+        harness.run_to(2);
+        let world = harness.app.world_mut();
+        let shots: Vec<_> = world.query::<(&Projectile, &Velocity)>().iter(world).map(|(p, v)| (*p, *v)).collect();
+        let [(projectile, vel)] = shots[..] else {
+            panic!("expected one projectile, got {}", shots.len());
+        };
+        assert_eq!(projectile.radius, 9.);
+        assert_eq!((vel.x, vel.y), (300., 0.));
+    }
+
+    fn charged_shot(held: u32) -> (Projectile, Velocity) {
+        let script = InputScript::parse(&format!("1 0 f\n{} 0 -", held + 1)).unwrap();
+        let mut harness = Harness::new(1, script);
+        harness.app.insert_resource(ProjectileConfig { speed: 300., radius: 6., bounces: 0 });
+        harness.run_to(held + 1);
+        let world = harness.app.world_mut();
+        let shots: Vec<_> = world.query::<(&Projectile, &Velocity)>().iter(world).map(|(p, v)| (*p, *v)).collect();
+        assert_eq!(shots.len(), 1, "held for {held} frames");
+        shots[0]
+    }
+
+    #[test]
+    fn charging_longer_hits_harder_up_to_the_cap() {
+        let (tap, tap_vel) = charged_shot(1);
+        let (half, half_vel) = charged_shot(MAX_CHARGE_FRAMES / 2 + 1);
+        let (full, full_vel) = charged_shot(MAX_CHARGE_FRAMES * 2);
+        assert_eq!([tap.damage, half.damage, full.damage], [PROJECTILE_DAMAGE, 15, 2 * PROJECTILE_DAMAGE]);
+        assert_eq!(tap_vel.x, 300.);
+        assert_eq!(half_vel.x, 300. * 1.5);
+        assert_eq!(full_vel.x, 300. * 2.);
+    }
+
+    #[test]
+    fn an_empty_magazine_blocks_firing_until_it_reloads() {
+        let mut ammo = Ammo::default();
+        assert!((0..MAX_AMMO).all(|_| ammo.take()));
+        assert!(!ammo.take());
+        assert_eq!(ammo.reload, RELOAD_FRAMES);
+        (1..RELOAD_FRAMES).for_each(|_| ammo.tick());
+        assert_eq!(ammo.rounds, 0);
+        ammo.tick();
+        assert_eq!(ammo, Ammo::default());
+
+        // the first tap finds the magazine empty, the one after the reload fires
+        let script = format!("1 0 f\n2 0 -\n{} 0 f\n{} 0 -", RELOAD_FRAMES + 1, RELOAD_FRAMES + 2);
+        let mut harness = Harness::new(1, InputScript::parse(&script).unwrap());
+        let player = harness.player(0);
+        *harness.app.world_mut().get_mut::<Ammo>(player).unwrap() = Ammo { rounds: 0, reload: RELOAD_FRAMES };
+        let shots = |harness: &mut Harness| harness.app.world_mut().query::<&Projectile>().iter(harness.app.world()).count();
+        harness.run_to(2);
+        assert_eq!(shots(&mut harness), 0);
+        harness.run_to(RELOAD_FRAMES + 2);
+        assert_eq!(shots(&mut harness), 1);
+        assert_eq!(harness.app.world().get::<Ammo>(player).unwrap().rounds, MAX_AMMO - 1);
+    }
+
+    #[test]
+    fn a_beam_hurts_everyone_along_it_and_nobody_else() {
+        let mut harness = Harness::new(5, InputScript::parse("1 0 f\n2 0 -").unwrap());
+        harness.app.insert_resource(Weapon::Beam);
+        // the shooter, two in line, one off to the side and one behind
+        let spots = [(0., 0.), (150., 0.), (300., PLAYER_RADIUS), (200., 100.), (-100., 0.)];
+        for (id, (x, y)) in spots.into_iter().enumerate() {
+            let player = harness.player(id);
+            harness.app.world_mut().get_mut::<Transform>(player).unwrap().translation = Vec3::new(x, y, 0.);
+        }
+        harness.run_to(2);
+        let world = harness.app.world_mut();
+        let mut hurt: Vec<_> = world
+            .query::<(&Player, &Shield)>()
+            .iter(world)
+            .filter(|(_, shield)| shield.hp < SHIELD_MAX_HP)
+            .map(|(player, shield)| (player.id, SHIELD_MAX_HP - shield.hp))
+            .collect();
+        hurt.sort();
+        assert_eq!(hurt, [(1, PROJECTILE_DAMAGE), (2, PROJECTILE_DAMAGE)]);
+    }
+
+    #[test]
+    fn homing_projectile_curves_toward_a_standing_target() {
+        let mut world = World::new();
+        world.insert_resource(Arena { half_size: Vec2::new(600., 340.) });
+        // the shooter right next to it is never a target
+        spawn_rollback(&mut world, (Player { id: 0 }, Transform::from_xyz(0., -20., 0.)));
+        spawn_rollback(&mut world, (Player { id: 1 }, Transform::from_xyz(300., 300., 0.)));
+        let homing = spawn_rollback(&mut world, (
+            Projectile { owner: 0, radius: 6., damage: 1, spawned_at: 0, bounces: 0 },
+            Velocity { x: 400., y: 0. },
+            Transform::from_xyz(0., 0., 1.),
+            Homing,
+        ));
+
+        let angle_off = |world: &World| {
+            let vel = world.get::<Velocity>(homing).unwrap();
+            let pos = world.get::<Transform>(homing).unwrap().translation.truncate();
+            Vec2::new(vel.x, vel.y).angle_between(Vec2::new(300., 300.) - pos).abs()
+        };
+        let mut last = angle_off(&world);
+        for _ in 0..5 {
+            world.run_system_once(steer_homing);
+            let vel = *world.get::<Velocity>(homing).unwrap();
+            world.get_mut::<Transform>(homing).unwrap().translation += Vec3::new(vel.x, vel.y, 0.) * SPU;
+            assert!((Vec2::new(vel.x, vel.y).length() - 400.).abs() < 1e-3, "keeps its speed");
+            let now = angle_off(&world);
+            assert!(now < last, "turning toward the target: {now} after {last}");
+            last = now;
+        }
+        // a limited turn rate, so it's still off after a few frames
+        assert!(last > 0.5, "{last}");
+        let vel = world.get::<Velocity>(homing).unwrap();
+        assert!(vel.y > 0. && vel.y < vel.x);
+    }
+
+    #[test]
+    fn without_ricochets_a_shot_is_gone_on_its_first_wall() {
+        let mut harness = Harness::new(1, InputScript::parse("1 0 f\n2 0 -").unwrap());
+        let player = harness.player(0);
+        let world = harness.app.world_mut();
+        world.get_mut::<Transform>(player).unwrap().translation = Vec3::ZERO;
+        world.spawn((Transform::from_xyz(150., 0., 0.), Wall::solid(Vec2::new(10., 100.)), CollisionLayer::wall()));
+        let shots = |harness: &mut Harness| harness.app.world_mut().query::<&Projectile>().iter(harness.app.world()).count();
+        harness.run_to(2);
+        assert_eq!(shots(&mut harness), 1);
+        harness.run_to(20);
+        assert_eq!(shots(&mut harness), 0);
+    }
+}
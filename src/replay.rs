@@ -0,0 +1,509 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy_ggrs::{GgrsSchedule, LocalInputs, PlayerInputs};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::input::InputPacked;
+use crate::sim::FrameCounter;
+use crate::{Config, Player, PLAYER_RADIUS};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayFrame {
+    pub frame: u32,
+    pub inputs: Vec<u8>,
+    pub positions: Vec<Vec2>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Replay {
+    pub players: usize,
+    pub frames: Vec<ReplayFrame>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReplayFormat {
+    #[default]
+    Text,
+    Bin,
+    Json,
+}
+
+impl ReplayFormat {
+    // ghosts are loaded by extension, anything unknown is read as text
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("bin") => ReplayFormat::Bin,
+            Some("json") => ReplayFormat::Json,
+            _ => ReplayFormat::Text,
+        }
+    }
+}
+
+// serde mirror of `Replay`, keeps glam's serde feature out of the build
+#[derive(Serialize, Deserialize)]
+struct StoredFrame {
+    frame: u32,
+    inputs: Vec<u8>,
+    positions: Vec<[f32; 2]>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredReplay {
+    players: usize,
+    frames: Vec<StoredFrame>,
+}
+
+impl From<&Replay> for StoredReplay {
+    fn from(replay: &Replay) -> Self {
+        StoredReplay {
+            players: replay.players,
+            frames: replay
+                .frames
+                .iter()
+                .map(|f| StoredFrame {
+                    frame: f.frame,
+                    inputs: f.inputs.clone(),
+                    positions: f.positions.iter().map(|p| p.to_array()).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<StoredReplay> for Replay {
+    fn from(stored: StoredReplay) -> Self {
+        Replay {
+            players: stored.players,
+            frames: stored
+                .frames
+                .into_iter()
+                .map(|f| ReplayFrame {
+                    frame: f.frame,
+                    inputs: f.inputs,
+                    positions: f.positions.into_iter().map(Vec2::from).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Replay {
+    // a rolled back frame overwrites itself and everything recorded after it
+    pub fn record(&mut self, entry: ReplayFrame) {
+        let keep = self.frames.partition_point(|f| f.frame < entry.frame);
+        self.frames.truncate(keep);
+        self.frames.push(entry);
+    }
+
+    // one line per frame: `frame input.. ; x y ..`
+    pub fn to_text(&self) -> String {
+        let mut text = format!("players {}\n", self.players);
+        for f in &self.frames {
+            let inputs: Vec<String> = f.inputs.iter().map(|i| i.to_string()).collect();
+            let positions: Vec<String> = f.positions.iter().map(|p| format!("{} {}", p.x, p.y)).collect();
+            text += &format!("{} {} ; {}\n", f.frame, inputs.join(" "), positions.join(" "));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let players = lines
+            .next()
+            .and_then(|l| l.strip_prefix("players "))
+            .and_then(|n| n.trim().parse().ok())
+            .ok_or("missing players header")?;
+
+        let mut replay = Replay { players, frames: Vec::new() };
+        for (n, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let bad = || format!("bad replay line {}", n + 2);
+            let (head, tail) = line.split_once(';').ok_or_else(bad)?;
+            let mut head = head.split_whitespace();
+            let frame = head.next().and_then(|f| f.parse().ok()).ok_or_else(bad)?;
+            let inputs: Vec<u8> = head.map(|i| i.parse().map_err(|_| bad())).collect::<Result<_, _>>()?;
+            let coords: Vec<f32> =
+                tail.split_whitespace().map(|c| c.parse().map_err(|_| bad())).collect::<Result<_, _>>()?;
+            if inputs.len() != players || coords.len() != players * 2 {
+                return Err(bad());
+            }
+            let positions = coords.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect();
+            replay.frames.push(ReplayFrame { frame, inputs, positions });
+        }
+        Ok(replay)
+    }
+
+    pub fn encode(&self, format: ReplayFormat) -> Result<Vec<u8>, String> {
+        let stored = StoredReplay::from(self);
+        match format {
+            ReplayFormat::Text => Ok(self.to_text().into_bytes()),
+            ReplayFormat::Bin => bincode::serialize(&stored).map_err(|e| e.to_string()),
+            ReplayFormat::Json => serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn decode(bytes: &[u8], format: ReplayFormat) -> Result<Self, String> {
+        let stored: StoredReplay = match format {
+            ReplayFormat::Text => {
+                let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                return Self::from_text(text);
+            }
+            ReplayFormat::Bin => bincode::deserialize(bytes).map_err(|e| e.to_string())?,
+            ReplayFormat::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string())?,
+        };
+        Ok(stored.into())
+    }
+
+    pub fn load(path: &PathBuf) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::decode(&bytes, ReplayFormat::from_path(path)).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+// First frame two replays disagree on, for tracking down where peers desynced. Recorded
+// positions are the simulated state, so comparing them needs no re-simulation.
+pub fn first_divergence(a: &Replay, b: &Replay) -> Option<String> {
+    if a.players != b.players {
+        return Some(format!("player counts differ: {} vs {}", a.players, b.players));
+    }
+    for (fa, fb) in a.frames.iter().zip(&b.frames) {
+        if fa.frame != fb.frame {
+            let frame = fa.frame.min(fb.frame);
+            return Some(format!("frame {frame}: only recorded in one of them"));
+        }
+        if fa.inputs != fb.inputs {
+            return Some(format!("frame {}: inputs {:?} vs {:?}", fa.frame, fa.inputs, fb.inputs));
+        }
+        let differing = fa.positions.iter().zip(&fb.positions).enumerate().find(|(_, (pa, pb))| pa != pb);
+        if let Some((id, (pa, pb))) = differing {
+            return Some(format!("frame {}: player {id} at {pa} vs {pb}", fa.frame));
+        }
+    }
+    match (a.frames.get(b.frames.len()), b.frames.get(a.frames.len())) {
+        (Some(f), _) | (_, Some(f)) => Some(format!("frame {}: only recorded in one of them", f.frame)),
+        _ => None,
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    pub path: PathBuf,
+    pub format: ReplayFormat,
+    pub replay: Replay,
+}
+
+pub fn record_replay(
+    mut recorder: ResMut<ReplayRecorder>,
+    frame: Res<FrameCounter>,
+    inputs: Res<PlayerInputs<Config>>,
+    players: Query<(&Transform, &Player)>
+) {
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(_, player)| player.id);
+    recorder.replay.record(ReplayFrame {
+        frame: frame.0,
+        inputs: inputs.iter().map(|(input, _)| input.wasd).collect(),
+        positions: players.iter().map(|(t, _)| t.translation.truncate()).collect(),
+    });
+}
+
+pub fn save_replay_on_exit(mut exit: EventReader<AppExit>, recorder: Res<ReplayRecorder>) {
+    if exit.read().next().is_none() {
+        return;
+    }
+    let written = recorder
+        .replay
+        .encode(recorder.format)
+        .and_then(|bytes| fs::write(&recorder.path, bytes).map_err(|e| e.to_string()));
+    match written {
+        Ok(()) => info!("replay saved to {}", recorder.path.display()),
+        Err(e) => error!("failed to save replay to {}: {e}", recorder.path.display()),
+    }
+}
+
+#[derive(Resource)]
+pub struct GhostReplay(pub Replay);
+
+const SEEK_BACK_KEY: KeyCode = KeyCode::BracketLeft;
+const SEEK_FORWARD_KEY: KeyCode = KeyCode::BracketRight;
+const SEEK_FRAMES: i64 = 60;
+
+// How far the ghosts are ahead of (or behind) the live frame. Seeking only moves the ghosts
+// along the recording: a replay holds the state of every frame, and re-running its inputs
+// from the start lands on exactly that state (see the tests). Taking the world itself back
+// or forward is what `Playback` is for.
+#[derive(Resource, Default)]
+pub struct GhostSeek {
+    pub offset: i64,
+}
+
+pub fn ghost_frame(frame: u32, seek: &GhostSeek) -> u32 {
+    (frame as i64 + seek.offset).clamp(0, u32::MAX as i64) as u32
+}
+
+fn seek_step(input: &ButtonInput<KeyCode>) -> Option<i64> {
+    match (input.just_pressed(SEEK_BACK_KEY), input.just_pressed(SEEK_FORWARD_KEY)) {
+        (true, false) => Some(-SEEK_FRAMES),
+        (false, true) => Some(SEEK_FRAMES),
+        _ => None,
+    }
+}
+
+pub fn seek_ghosts(input: Res<ButtonInput<KeyCode>>, mut seek: ResMut<GhostSeek>, frame: Res<FrameCounter>) {
+    let Some(step) = seek_step(&input) else {
+        return;
+    };
+    // no point going back past the first frame
+    seek.offset = (seek.offset + step).max(-(frame.0 as i64));
+    info!("ghosts at frame {}", ghost_frame(frame.0, &seek));
+}
+
+// A recording driving the simulation in place of live input. Seeking rebuilds the world
+// from before frame 1 and re-simulates the recorded inputs up to the frame asked for, which
+// lands on the recorded state since the simulation is deterministic. Playback runs as a
+// synctest that never rolls back, the snapshots from before a seek would undo it.
+#[derive(Resource)]
+pub struct Playback {
+    pub replay: Replay,
+    seek_to: Option<u32>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback { replay, seek_to: None }
+    }
+
+    // `frame` is the next one simulated, everything before it is run again
+    pub fn seek(&mut self, frame: u32) {
+        self.seek_to = Some(frame.max(1));
+    }
+
+    // nobody presses anything on frames missing from the recording
+    pub fn input_at(&self, handle: usize, frame: u32) -> InputPacked {
+        let wasd = match self.replay.frames.binary_search_by_key(&frame, |f| f.frame) {
+            Ok(i) => self.replay.frames[i].inputs.get(handle).copied().unwrap_or(0),
+            Err(_) => 0,
+        };
+        InputPacked::new(wasd)
+    }
+}
+
+// Goes by the frame counter rather than ggrs' frame, the two part ways on the first seek.
+// Without rollbacks the counter is always the last frame simulated.
+pub fn read_playback_inputs(
+    mut local_inputs: ResMut<LocalInputs<Config>>,
+    playback: Res<Playback>,
+    frame: Res<FrameCounter>
+) {
+    for (handle, input) in local_inputs.0.iter_mut() {
+        *input = playback.input_at(*handle, frame.0 + 1);
+    }
+}
+
+pub fn seek_playback(input: Res<ButtonInput<KeyCode>>, mut playback: ResMut<Playback>, frame: Res<FrameCounter>) {
+    let Some(step) = seek_step(&input) else {
+        return;
+    };
+    let to = (frame.0 as i64 + 1 + step).clamp(1, u32::MAX as i64) as u32;
+    playback.seek(to);
+    info!("playback seeking to frame {to}");
+}
+
+fn set_playback_inputs(world: &mut World, frame: u32) {
+    let inputs: Vec<_> = {
+        let playback = world.resource::<Playback>();
+        (0..playback.replay.players).map(|handle| playback.input_at(handle, frame)).collect()
+    };
+    let mut player_inputs = world.resource_mut::<PlayerInputs<Config>>();
+    for ((input, _), recorded) in player_inputs.iter_mut().zip(inputs) {
+        *input = recorded;
+    }
+}
+
+// Runs ahead of the frame ggrs is advancing: the world is rebuilt and simulated up to the
+// frame before the target, and the target frame itself is left to ggrs with its recorded
+// inputs swapped in.
+pub fn apply_playback_seek(world: &mut World) {
+    let Some(to) = world.resource_mut::<Playback>().seek_to.take() else {
+        return;
+    };
+    crate::restart_simulation(world);
+    for frame in 1..to {
+        set_playback_inputs(world, frame);
+        world.run_schedule(GgrsSchedule);
+    }
+    set_playback_inputs(world, to);
+}
+
+#[derive(Component)]
+pub struct Ghost {
+    id: usize,
+}
+
+// positions of the latest recorded frame not after `frame`
+pub fn ghost_positions(replay: &Replay, frame: u32) -> Option<&[Vec2]> {
+    let next = replay.frames.partition_point(|f| f.frame <= frame);
+    next.checked_sub(1).map(|i| replay.frames[i].positions.as_slice())
+}
+
+pub fn spawn_ghosts(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    ghosts: Res<GhostReplay>
+) {
+    let mesh = Mesh2dHandle(meshes.add(Circle::new(PLAYER_RADIUS)));
+    let material = materials.add(Color::srgba(1., 1., 1., 0.35));
+    for id in 0..ghosts.0.players {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Ghost { id },
+        ));
+    }
+}
+
+// ghosts are plain render entities, they never take part in the simulation
+pub fn update_ghosts(
+    ghosts: Res<GhostReplay>,
+    seek: Res<GhostSeek>,
+    frame: Res<FrameCounter>,
+    mut query: Query<(&mut Transform, &mut Visibility, &Ghost)>
+) {
+    let positions = ghost_positions(&ghosts.0, ghost_frame(frame.0, &seek));
+    for (mut transform, mut visibility, ghost) in query.iter_mut() {
+        match positions.and_then(|p| p.get(ghost.id)) {
+            Some(pos) => {
+                transform.translation = pos.extend(-1.);
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Harness;
+    use crate::projectile::Projectile;
+    use crate::script::InputScript;
+    use crate::sim::FrameHash;
+
+    fn record(players: usize, script: InputScript, frames: u32) -> Replay {
+        let mut harness = Harness::new(players, script);
+        harness.app.insert_resource(ReplayRecorder {
+            path: PathBuf::new(),
+            format: ReplayFormat::Text,
+            replay: Replay { players, frames: Vec::new() },
+        });
+        harness.run_to(frames);
+        harness.app.world().resource::<ReplayRecorder>().replay.clone()
+    }
+
+    #[test]
+    fn seeking_matches_resimulating_the_recorded_inputs() {
+        let script = InputScript::parse("1 0 ur\n20 1 l\n45 0 d\n50 1 -\n").unwrap();
+        let replay = record(2, script, 90);
+        assert_eq!(replay.frames.len(), 90);
+
+        // the recorded inputs alone, frame by frame, drive a fresh run
+        let steps = replay
+            .frames
+            .iter()
+            .flat_map(|f| f.inputs.iter().enumerate().map(|(handle, &wasd)| (f.frame as i32, handle, wasd)))
+            .collect();
+        let mut resim = Harness::new(2, InputScript::from_steps(steps));
+        for k in [1, 19, 20, 46, 90] {
+            resim.run_to(k);
+            let live: Vec<_> = resim.players().iter().map(|(_, pos, _)| *pos).collect();
+            // seen from frame 30 of the live game
+            let seek = GhostSeek { offset: k as i64 - 30 };
+            assert_eq!(ghost_positions(&replay, ghost_frame(30, &seek)), Some(live.as_slice()), "frame {k}");
+        }
+    }
+
+    // players as (id, position, velocity), the frame hash and how many shots are out
+    type State = (Vec<(usize, Vec2, f32, f32)>, u64, usize);
+
+    fn state(harness: &mut Harness) -> State {
+        let players = harness.players().into_iter().map(|(id, pos, vel)| (id, pos, vel.x, vel.y)).collect();
+        let hash = harness.app.world().resource::<FrameHash>().0;
+        let projectiles = harness.app.world_mut().query::<&Projectile>().iter(harness.app.world()).count();
+        (players, hash, projectiles)
+    }
+
+    #[test]
+    fn a_seeking_playback_lands_where_a_straight_run_does() {
+        let script = InputScript::parse("1 0 ur\n10 0 f\n11 0 -\n20 1 l\n45 0 d\n50 1 f\n51 1 -\n").unwrap();
+        let mut playback = Harness::playback(record(2, script.clone(), 120));
+        playback.run_to(90);
+
+        // back past the shots, forward past where the playback had got to, and to the start
+        for k in [40, 100, 12, 1] {
+            playback.app.world_mut().resource_mut::<Playback>().seek(k);
+            playback.app.update();
+            assert_eq!(playback.frame(), k);
+            let mut straight = Harness::new(2, script.clone());
+            straight.run_to(k);
+            assert_eq!(state(&mut playback), state(&mut straight), "frame {k}");
+        }
+        // and it carries on from there like the recording did
+        playback.run_to(60);
+        let mut straight = Harness::new(2, script);
+        straight.run_to(60);
+        assert_eq!(state(&mut playback), state(&mut straight));
+    }
+
+    fn sample() -> Replay {
+        let frame = |frame, x: f32| ReplayFrame { frame, inputs: vec![1, 2], positions: vec![Vec2::new(x, 0.), Vec2::new(-x, 1.)] };
+        Replay { players: 2, frames: vec![frame(1, 1.), frame(2, 2.), frame(5, 5.)] }
+    }
+
+    #[test]
+    fn ghosts_show_the_latest_recorded_frame_up_to_now() {
+        let replay = sample();
+        assert_eq!(ghost_positions(&replay, 0), None);
+        assert_eq!(ghost_positions(&replay, 2).unwrap()[0].x, 2.);
+        // frames missing from the recording hold the last one
+        assert_eq!(ghost_positions(&replay, 4).unwrap()[0].x, 2.);
+        assert_eq!(ghost_positions(&replay, 100).unwrap()[1], Vec2::new(-5., 1.));
+    }
+
+    #[test]
+    fn every_format_reads_back_what_it_wrote() {
+        let replay = record(2, InputScript::parse("1 0 ur\n12 1 l\n").unwrap(), 30);
+        for format in [ReplayFormat::Text, ReplayFormat::Bin, ReplayFormat::Json] {
+            let bytes = replay.encode(format).unwrap();
+            assert_eq!(Replay::decode(&bytes, format).unwrap(), replay, "{format:?}");
+        }
+        let sizes: Vec<_> = [ReplayFormat::Bin, ReplayFormat::Json].map(|f| replay.encode(f).unwrap().len()).into();
+        assert!(sizes[0] < sizes[1], "{sizes:?}");
+    }
+
+    #[test]
+    fn replays_that_part_ways_at_a_frame_are_reported_at_that_frame() {
+        let a = record(2, InputScript::parse("1 0 r\n1 1 u\n").unwrap(), 40);
+        assert_eq!(first_divergence(&a, &a.clone()), None);
+        // player 1 turns left at frame 23 in only one of them
+        let b = record(2, InputScript::parse("1 0 r\n1 1 u\n23 1 l\n").unwrap(), 40);
+        let report = first_divergence(&a, &b).unwrap();
+        assert!(report.starts_with("frame 23: inputs"), "{report}");
+
+        // same inputs but a desynced position
+        let mut desynced = a.clone();
+        let k = desynced.frames.iter().position(|f| f.frame == 31).unwrap();
+        desynced.frames[k].positions[0].x += 0.5;
+        let report = first_divergence(&a, &desynced).unwrap();
+        assert!(report.starts_with("frame 31: player 0 at"), "{report}");
+
+        // one cut short
+        desynced.frames.truncate(k);
+        assert_eq!(first_divergence(&a, &desynced).unwrap(), "frame 31: only recorded in one of them");
+    }
+}
@@ -0,0 +1,29 @@
+// splitmix64, small and identical on every platform
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1), from the top 24 bits
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+}
+
+// derives an independent seed from a base seed and a key, e.g. a player handle or frame
+pub fn mix(seed: u64, key: u64) -> u64 {
+    SimRng::new(seed ^ key.wrapping_mul(0x9e37_79b9_7f4a_7c15)).next_u64()
+}
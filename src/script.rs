@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::Path;
+use bevy::prelude::*;
+use bevy_ggrs::{LocalInputs, RollbackFrameCount};
+use crate::input::{InputLayout, InputPacked};
+use crate::Config;
+
+// Scripted inputs for headless regression runs, overriding the keyboard and bots for the
+// handles they mention. One change per line, held until that handle's next line:
+//   <frame> <handle> <buttons out of `udrlfmhs`, or `-` for none>
+// Frames count like `--max-frames` and the state dump, the first simulated frame is 1.
+// Paired with `--max-frames` and `--expect` this checks a run ends in a known state.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct InputScript {
+    // (frame, handle, buttons), sorted by frame
+    steps: Vec<(i32, usize, u8)>,
+}
+
+fn buttons(letters: &str) -> Option<u8> {
+    if letters == "-" {
+        return Some(0);
+    }
+    letters.chars().try_fold(0, |wasd, c| {
+        let button = match c {
+            'u' => InputLayout::Up,
+            'd' => InputLayout::Down,
+            'r' => InputLayout::Right,
+            'l' => InputLayout::Left,
+            'f' => InputLayout::Fire,
+            'm' => InputLayout::Magnet,
+            'h' => InputLayout::Hook,
+            's' => InputLayout::SlowField,
+            _ => return None,
+        };
+        Some(wasd | button.mask())
+    })
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [] => continue,
+                [frame, handle, letters] => {
+                    let frame = frame.parse().map_err(|_| format!("line {}: `{frame}` is not a frame", n + 1))?;
+                    let handle = handle.parse().map_err(|_| format!("line {}: `{handle}` is not a handle", n + 1))?;
+                    let wasd = buttons(letters)
+                        .ok_or_else(|| format!("line {}: buttons `{letters}` should only use `udrlfmhs` or be `-`", n + 1))?;
+                    steps.push((frame, handle, wasd));
+                }
+                _ => return Err(format!("line {}: can't read `{}`", n + 1, line.trim())),
+            }
+        }
+        // stable, so lines for the same frame and handle keep their order and the last one wins
+        steps.sort_by_key(|&(frame, ..)| frame);
+        Ok(Self { steps })
+    }
+
+    #[cfg(test)]
+    pub fn from_steps(mut steps: Vec<(i32, usize, u8)>) -> Self {
+        steps.sort_by_key(|&(frame, ..)| frame);
+        Self { steps }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    // None for a handle the script doesn't mention up to `frame`
+    pub fn input_at(&self, handle: usize, frame: i32) -> Option<u8> {
+        self.steps
+            .iter()
+            .take_while(|&&(at, ..)| at <= frame)
+            .filter(|&&(_, h, _)| h == handle)
+            .last()
+            .map(|&(.., wasd)| wasd)
+    }
+}
+
+// runs after read_local_inputs and replaces what it read for the scripted handles
+pub fn read_scripted_inputs(
+    mut local_inputs: ResMut<LocalInputs<Config>>,
+    script: Res<InputScript>,
+    frame: Res<RollbackFrameCount>
+) {
+    // the rollback count is one behind the frame counter of the frame these inputs are for
+    let frame = frame.0 + 1;
+    for (handle, input) in local_inputs.0.iter_mut() {
+        if let Some(wasd) = script.input_at(*handle, frame) {
+            *input = InputPacked::new(wasd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_hold_until_the_next_one_for_that_handle() {
+        let script = InputScript::parse("# warm up\n10 0 ur\n5 1 f  # fire\n\n20 0 -\n").unwrap();
+        assert_eq!(script.input_at(0, 9), None);
+        assert_eq!(script.input_at(0, 10), Some(InputLayout::Up.mask() | InputLayout::Right.mask()));
+        assert_eq!(script.input_at(0, 19), script.input_at(0, 10));
+        assert_eq!(script.input_at(0, 20), Some(0));
+        assert_eq!(script.input_at(1, 100), Some(InputLayout::Fire.mask()));
+        assert_eq!(script.input_at(2, 100), None);
+    }
+
+    #[test]
+    fn bad_lines_name_the_line() {
+        assert_eq!(InputScript::parse("1 0 r\nx 0 r").unwrap_err(), "line 2: `x` is not a frame");
+        assert_eq!(
+            InputScript::parse("1 0 q").unwrap_err(),
+            "line 1: buttons `q` should only use `udrlfmhs` or be `-`"
+        );
+        assert!(InputScript::parse("1 0").is_err());
+    }
+}
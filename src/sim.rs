@@ -0,0 +1,252 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use clap::ValueEnum;
+use crate::collision::CollisionKind;
+use crate::input::InputLayout;
+use crate::Velocity;
+
+// floats are hashed in steps of this size, so last-bit noise doesn't read as a desync
+const HASH_QUANTUM: f32 = 1. / 1024.;
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FrameCounter(pub u32);
+
+impl FrameCounter {
+    pub fn elapsed_since(&self, frame: u32, frames: u32) -> bool {
+        self.0.wrapping_sub(frame) >= frames
+    }
+}
+
+// nobody moves while the pre-game countdown runs
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct Countdown {
+    pub frames: u32,
+}
+
+impl Countdown {
+    pub fn active(&self, frame: u32) -> bool {
+        frame <= self.frames
+    }
+
+    pub fn remaining(&self, frame: u32) -> u32 {
+        self.frames.saturating_sub(frame)
+    }
+}
+
+pub fn advance_frame_counter(
+    mut counter: ResMut<FrameCounter>,
+    mut frame_hash: ResMut<FrameHash>,
+    mut events: ResMut<SimEvents>
+) {
+    counter.0 = counter.0.wrapping_add(1);
+    frame_hash.0 = 0;
+    events.0.clear();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimEvent {
+    Collision { kind: CollisionKind, a: Entity, b: Entity, point: Vec2 },
+    // a player moved somewhere else in one go, through a portal or by respawning
+    Relocated { player: usize, to: Vec2 },
+}
+
+// What happened in the latest simulated frame, in the same order on every peer, for
+// sounds, camera shake and the like to react to. It's rolled back like the rest of the
+// state, so events of a mispredicted frame are replaced when it's simulated again.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct SimEvents(pub Vec<SimEvent>);
+
+// `T` names the ability, so one entity can carry a cooldown per ability
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Cooldown<T: Send + Sync + 'static> {
+    pub frames: u32,
+    pub ready_at: u32,
+    ability: PhantomData<T>,
+}
+
+impl<T: Send + Sync + 'static> Cooldown<T> {
+    pub fn new(frames: u32) -> Self {
+        Self { frames, ready_at: 0, ability: PhantomData }
+    }
+
+    pub fn trigger(&mut self, now: u32) {
+        self.ready_at = now + self.frames;
+    }
+}
+
+pub fn ready<T: Send + Sync + 'static>(cooldown: &Cooldown<T>, now: u32) -> bool {
+    now >= cooldown.ready_at
+}
+
+pub fn quantize(v: f32) -> i64 {
+    (v / HASH_QUANTUM).round() as i64
+}
+
+// gameplay state folded in by systems during the current frame, part of the ggrs checksum
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FrameHash(pub u64);
+
+#[derive(SystemParam)]
+pub struct SimHasher<'w> {
+    frame_hash: ResMut<'w, FrameHash>,
+}
+
+impl SimHasher<'_> {
+    // contributions are summed, so entities can be fed in query order
+    pub fn hash<T: Hash>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        self.frame_hash.0 = self.frame_hash.0.wrapping_add(hasher.finish());
+    }
+
+    pub fn hash_f32(&mut self, v: f32) {
+        self.hash(&quantize(v));
+    }
+
+    pub fn hash_vec2(&mut self, v: Vec2) {
+        self.hash(&(quantize(v.x), quantize(v.y)));
+    }
+}
+
+fn axis(wasd: u8, positive: InputLayout, negative: InputLayout) -> f32 {
+    (positive.is_set(wasd) as i32 - negative.is_set(wasd) as i32) as f32
+}
+
+pub fn compute_velocity(wasd: u8, speed: f32) -> Velocity {
+    Velocity {
+        x: axis(wasd, InputLayout::Right, InputLayout::Left) * speed,
+        y: axis(wasd, InputLayout::Up, InputLayout::Down) * speed,
+    }
+}
+
+// keeps stacked knockback and forces from launching anything across the arena in a frame
+pub const MAX_SPEED: f32 = 800.;
+
+pub fn clamp_speed(vel: Velocity, max: f32) -> Velocity {
+    let v = Vec2::new(vel.x, vel.y).clamp_length_max(max);
+    Velocity { x: v.x, y: v.y }
+}
+
+// Whether this frame's forces move players this frame. Semi-implicit applies them to the
+// velocity first and moves with the result. Explicit Euler moves with last frame's
+// velocity before working out this one, so it trails by a frame: a constant gap under a
+// steady velocity, a growing one under a pull that builds up like the gravity well's,
+// and input is answered a frame later.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Integrator {
+    Euler,
+    #[default]
+    SemiImplicit,
+}
+
+pub fn explicit_euler(integrator: Res<Integrator>) -> bool {
+    *integrator == Integrator::Euler
+}
+
+// where a body was before this frame's move, whichever integrator did the moving
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct PrevPosition(pub Vec2);
+
+pub fn integrate(pos: Vec3, vel: Velocity, dt: f32) -> Vec3 {
+    Vec3 {
+        x: pos.x + vel.x * dt,
+        y: pos.y + vel.y * dt,
+        z: pos.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+
+    #[test]
+    fn clamp_speed_caps_length_and_keeps_direction() {
+        let capped = clamp_speed(Velocity { x: 3000., y: 4000. }, MAX_SPEED);
+        assert!((Vec2::new(capped.x, capped.y).length() - MAX_SPEED).abs() < 1e-3);
+        assert!((capped.x / capped.y - 0.75).abs() < 1e-6);
+
+        let slow = clamp_speed(Velocity { x: 30., y: -40. }, MAX_SPEED);
+        assert_eq!((slow.x, slow.y), (30., -40.));
+    }
+
+    #[test]
+    fn every_direction_combination_moves_the_right_way() {
+        let buttons = [InputLayout::Up, InputLayout::Down, InputLayout::Left, InputLayout::Right];
+        for combo in 0..16u8 {
+            let wasd = buttons.iter().enumerate().filter(|(i, _)| combo & (1 << i) != 0).fold(0, |w, (_, b)| w | b.mask());
+            let held = |i: usize| (combo & (1 << i) != 0) as i32 as f32;
+            let vel = compute_velocity(wasd, 200.);
+            // opposite buttons cancel out
+            assert_eq!(vel.x, (held(3) - held(2)) * 200., "combo {combo:04b}");
+            assert_eq!(vel.y, (held(0) - held(1)) * 200., "combo {combo:04b}");
+        }
+    }
+
+    #[test]
+    fn integration_scales_with_dt_and_keeps_depth() {
+        let vel = Velocity { x: 120., y: -60. };
+        for dt in [1. / 30., 1. / 60., 1. / 144.] {
+            let pos = integrate(Vec3::new(10., 20., 3.), vel, dt);
+            assert_eq!(pos, Vec3::new(10. + 120. * dt, 20. - 60. * dt, 3.));
+        }
+        assert_eq!(integrate(Vec3::ONE, vel, 0.), Vec3::ONE);
+    }
+
+    #[test]
+    fn frame_counter_follows_the_schedule_through_rollbacks() {
+        // synctest rolls back and resimulates the last frames on every step, a counter that
+        // wasn't rolled back with them would run ahead of the session
+        let mut harness = crate::harness::Harness::new(1, Default::default());
+        for _ in 0..40 {
+            harness.app.update();
+            let session_frame = harness.app.world().resource::<bevy_ggrs::RollbackFrameCount>().0;
+            assert_eq!(harness.frame() as i32, session_frame);
+        }
+        assert!(harness.frame() >= 39);
+    }
+
+    #[test]
+    fn elapsed_since_counts_across_wraparound() {
+        assert!(!FrameCounter(10).elapsed_since(5, 6));
+        assert!(FrameCounter(11).elapsed_since(5, 6));
+        assert!(FrameCounter(2).elapsed_since(u32::MAX - 3, 6));
+    }
+
+    fn frame_hash(values: Vec<(Vec2, f32)>) -> u64 {
+        let mut world = World::new();
+        world.init_resource::<FrameHash>();
+        world.run_system_once(move |mut hasher: SimHasher| {
+            for (v, f) in &values {
+                hasher.hash_vec2(*v);
+                hasher.hash_f32(*f);
+            }
+        });
+        world.resource::<FrameHash>().0
+    }
+
+    #[test]
+    fn states_equal_after_quantizing_hash_equal() {
+        let state = vec![(Vec2::new(12.5, -3.), 0.25), (Vec2::new(-400., 7.75), 1.)];
+        let nudged = state.iter().map(|&(v, f)| (v + HASH_QUANTUM / 8., f - HASH_QUANTUM / 8.)).collect();
+        assert_eq!(frame_hash(state.clone()), frame_hash(nudged));
+        // contributions sum, so entity order doesn't matter
+        assert_eq!(frame_hash(state.clone()), frame_hash(state.iter().rev().copied().collect()));
+
+        let moved = vec![(Vec2::new(12.5 + HASH_QUANTUM, -3.), 0.25), state[1]];
+        assert_ne!(frame_hash(state), frame_hash(moved));
+    }
+
+    #[test]
+    fn cooldown_is_ready_again_once_its_frames_have_passed() {
+        struct Dash;
+        let mut cooldown = Cooldown::<Dash>::new(15);
+        assert!(ready(&cooldown, 0), "ready before first use");
+        cooldown.trigger(100);
+        assert!((100..115).all(|now| !ready(&cooldown, now)));
+        assert!(ready(&cooldown, 115));
+        assert!(ready(&cooldown, 1000));
+    }
+}
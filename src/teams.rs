@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use crate::sim::FrameCounter;
+use crate::UPS;
+
+pub const TEAM_COUNT: usize = 2;
+// speed given up per second of lead over the best other team, and gained per second behind
+const HANDICAP_PER_SECOND: f32 = 0.01;
+const MAX_HANDICAP: f32 = 0.25;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Team(pub usize);
+
+impl Team {
+    // players alternate between teams by handle
+    pub fn of_player(id: usize) -> Self {
+        Team(id % TEAM_COUNT)
+    }
+
+    pub fn color(self) -> Color {
+        match self.0 {
+            0 => Color::srgb(1., 0.3, 0.3),
+            _ => Color::srgb(0.3, 0.5, 1.),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Score {
+    pub teams: [u32; TEAM_COUNT],
+}
+
+// leading teams move slower and trailing ones faster, see `handicap_factor`
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Handicap;
+
+pub fn handicap_factor(score: &Score, team: Team) -> f32 {
+    let best_other = (0..TEAM_COUNT).filter(|&t| t != team.0).map(|t| score.teams[t]).max().unwrap_or(0);
+    let lead = (score.teams[team.0] as f32 - best_other as f32) / UPS;
+    (1. - lead * HANDICAP_PER_SECOND).clamp(1. - MAX_HANDICAP, 1. + MAX_HANDICAP)
+}
+
+pub const TEAM_ENERGY_MAX: u32 = 100;
+
+// Energy each team shares for its abilities, refilled by `regen_per_second`. Members
+// spend it in handle order, so who gets it when it runs short doesn't depend on
+// query order.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TeamEnergy {
+    pub pools: [u32; TEAM_COUNT],
+    pub regen_per_second: u32,
+}
+
+impl TeamEnergy {
+    pub fn new(regen_per_second: u32) -> Self {
+        TeamEnergy { pools: [TEAM_ENERGY_MAX; TEAM_COUNT], regen_per_second }
+    }
+
+    pub fn spend(&mut self, team: Team, cost: u32) -> bool {
+        let pool = &mut self.pools[team.0];
+        let enough = *pool >= cost;
+        if enough {
+            *pool -= cost;
+        }
+        enough
+    }
+
+    // whole points due on `frame`, so a second adds up to exactly `regen_per_second`
+    pub fn regen_at(&self, frame: u32) -> u32 {
+        let (rate, ups, frame) = (self.regen_per_second as u64, UPS as u64, frame as u64);
+        (rate * (frame + 1) / ups - rate * frame / ups) as u32
+    }
+}
+
+pub fn regen_team_energy(mut energy: ResMut<TeamEnergy>, frame: Res<FrameCounter>) {
+    let regen = energy.regen_at(frame.0);
+    for pool in energy.pools.iter_mut() {
+        *pool = (*pool + regen).min(TEAM_ENERGY_MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+
+    #[test]
+    fn the_leader_slows_down_and_the_trailer_speeds_up_with_the_gap() {
+        let factors = |teams| {
+            let score = Score { teams };
+            (handicap_factor(&score, Team(0)), handicap_factor(&score, Team(1)))
+        };
+        assert_eq!(factors([0, 0]), (1., 1.));
+        assert_eq!(factors([300, 300]), (1., 1.));
+        // ten seconds of capture ahead is a 10% difference either way
+        let (leader, trailer) = factors([10 * UPS as u32, 0]);
+        assert!((leader - 0.9).abs() < 1e-6 && (trailer - 1.1).abs() < 1e-6, "{leader} {trailer}");
+        assert_eq!(factors([0, 60 * UPS as u32]), (1. + MAX_HANDICAP, 1. - MAX_HANDICAP));
+    }
+
+    #[test]
+    fn teammates_spend_from_one_pool_that_refills_at_the_configured_rate() {
+        let mut energy = TeamEnergy::new(25);
+        // two teammates going on the same frame both pay, a third can't afford it
+        assert!(energy.spend(Team(0), 40));
+        assert!(energy.spend(Team(0), 40));
+        assert!(!energy.spend(Team(0), 40));
+        assert_eq!(energy.pools, [TEAM_ENERGY_MAX - 80, TEAM_ENERGY_MAX]);
+
+        for rate in [1, 25, 60, 61, 150] {
+            assert_eq!((0..UPS as u32).map(|f| TeamEnergy::new(rate).regen_at(f)).sum::<u32>(), rate, "{rate}");
+        }
+        let mut world = World::new();
+        world.insert_resource(TeamEnergy { pools: [0, TEAM_ENERGY_MAX - 10], regen_per_second: 25 });
+        for frame in 0..UPS as u32 {
+            world.insert_resource(FrameCounter(frame));
+            world.run_system_once(regen_team_energy);
+        }
+        assert_eq!(world.resource::<TeamEnergy>().pools, [25, TEAM_ENERGY_MAX]);
+    }
+}
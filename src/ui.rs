@@ -0,0 +1,512 @@
+use bevy::color::HexColorError;
+use bevy::prelude::*;
+use bevy_ggrs::{LocalPlayers, Session};
+use clap::ValueEnum;
+use crate::combat::{Combo, Health};
+use crate::modes::{sumo_winner, zone_survivor, KnockedOut};
+use crate::net::catching_up;
+use crate::projectile::{Ammo, MAX_AMMO};
+use crate::sim::{Countdown, FrameCounter};
+use crate::teams::{Score, Team, TeamEnergy, TEAM_ENERGY_MAX};
+use crate::{Config, Player, UPS};
+
+pub fn parse_hex_color(hex: &str) -> Result<Color, String> {
+    Srgba::hex(hex).map(Color::from).map_err(|e| match e {
+        HexColorError::Length => format!("`{hex}` should be #rgb, #rgba, #rrggbb or #rrggbbaa"),
+        HexColorError::Char(c) => format!("`{hex}` has a non-hex character `{c}`"),
+        HexColorError::Parse(e) => format!("`{hex}`: {e}"),
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    #[default]
+    Default,
+    CbSafe,
+}
+
+// Okabe-Ito, with white in place of black so it shows on the dark background
+const CB_SAFE_PALETTE: [(u8, u8, u8); 8] = [
+    (0xE6, 0x9F, 0x00),
+    (0x56, 0xB4, 0xE9),
+    (0x00, 0x9E, 0x73),
+    (0xF0, 0xE4, 0x42),
+    (0x00, 0x72, 0xB2),
+    (0xD5, 0x5E, 0x00),
+    (0xCC, 0x79, 0xA7),
+    (0xFF, 0xFF, 0xFF),
+];
+
+pub fn palette_color(palette: Palette, id: usize, players_num: usize) -> Color {
+    match palette {
+        Palette::Default => Color::hsv(1., 1., 1. / (players_num * id) as f32),
+        Palette::CbSafe => {
+            let (r, g, b) = CB_SAFE_PALETTE[id % CB_SAFE_PALETTE.len()];
+            Color::srgb_u8(r, g, b)
+        }
+    }
+}
+
+// players without a configured color fall back to the palette
+pub fn player_color(colors: &[Color], palette: Palette, id: usize, players_num: usize) -> Color {
+    colors.get(id).copied().unwrap_or_else(|| palette_color(palette, id, players_num))
+}
+
+#[derive(Resource)]
+pub struct InputDelayIndicator {
+    pub frames: usize,
+}
+
+pub fn delay_ms(frames: usize, ups: f32) -> f32 {
+    frames as f32 * 1000. / ups
+}
+
+pub fn spawn_input_delay_indicator(mut commands: Commands, indicator: Res<InputDelayIndicator>) {
+    let text = format!(
+        "input delay: {} frames ({:.0} ms)",
+        indicator.frames,
+        delay_ms(indicator.frames, UPS)
+    );
+    commands.spawn(
+        TextBundle::from_section(text, TextStyle {
+            font_size: 18.,
+            color: Color::WHITE,
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+    );
+}
+
+#[derive(Component)]
+pub struct CountdownText;
+
+pub fn countdown_label(countdown: &Countdown, frame: u32) -> Option<String> {
+    let remaining = countdown.remaining(frame);
+    if remaining > 0 {
+        return Some(remaining.div_ceil(UPS as u32).to_string());
+    }
+    // show GO for a second after the countdown ends
+    let since_end = frame.saturating_sub(countdown.frames);
+    (countdown.frames > 0 && since_end < UPS as u32).then(|| "GO!".to_string())
+}
+
+pub fn spawn_countdown_text(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(35.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle {
+                    font_size: 96.,
+                    color: Color::WHITE,
+                    ..default()
+                }),
+                CountdownText,
+            ));
+        });
+}
+
+pub fn update_countdown_text(
+    mut query: Query<&mut Text, With<CountdownText>>,
+    countdown: Res<Countdown>,
+    frame: Res<FrameCounter>
+) {
+    let label = countdown_label(&countdown, frame.0).unwrap_or_default();
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value.clone_from(&label);
+        }
+    }
+}
+
+// on in modes that score, capture point and capture the flag
+#[derive(Resource)]
+pub struct ShowScore;
+
+#[derive(Component)]
+pub struct ScoreText;
+
+pub fn spawn_score_text(mut commands: Commands) {
+    let sections = (0..crate::teams::TEAM_COUNT).flat_map(|team| {
+        let separator = if team == 0 { "" } else { " - " };
+        [
+            TextSection::new(separator, TextStyle { font_size: 32., color: Color::WHITE, ..default() }),
+            TextSection::new("0", TextStyle { font_size: 32., color: Team(team).color(), ..default() }),
+        ]
+    });
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((TextBundle::from_sections(sections), ScoreText));
+        });
+}
+
+// score is kept in frames held, shown in seconds
+pub fn update_score_text(mut query: Query<&mut Text, With<ScoreText>>, score: Res<Score>) {
+    for mut text in query.iter_mut() {
+        for (team, points) in score.teams.iter().enumerate() {
+            let label = (points / UPS as u32).to_string();
+            if text.sections[team * 2 + 1].value != label {
+                text.sections[team * 2 + 1].value = label;
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CatchUpText;
+
+pub fn spawn_catch_up_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font_size: 24.,
+            color: Color::srgb(1., 0.8, 0.2),
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        CatchUpText,
+    ));
+}
+
+// hidden while the spectator keeps up
+pub fn catch_up_label(behind: usize) -> Option<String> {
+    catching_up(behind).then(|| format!("catching up ({behind} frames behind)"))
+}
+
+pub fn update_catch_up_text(
+    mut query: Query<(&mut Text, &mut Visibility), With<CatchUpText>>,
+    session: Res<Session<Config>>
+) {
+    let Session::Spectator(s) = &*session else {
+        return;
+    };
+    let label = catch_up_label(s.frames_behind_host());
+    for (mut text, mut visibility) in query.iter_mut() {
+        match &label {
+            Some(label) => {
+                *visibility = Visibility::Visible;
+                text.sections[0].value.clone_from(label);
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct AmmoText;
+
+pub fn spawn_ammo_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font_size: 18.,
+            color: Color::WHITE,
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            right: Val::Px(8.),
+            ..default()
+        }),
+        AmmoText,
+    ));
+}
+
+pub fn ammo_label(id: usize, ammo: &Ammo) -> String {
+    if ammo.reload > 0 {
+        format!("P{id} reloading ({:.1}s)", ammo.reload as f32 / UPS)
+    } else {
+        format!("P{id} ammo {}/{MAX_AMMO}", ammo.rounds)
+    }
+}
+
+// one line per local player
+pub fn update_ammo_text(
+    mut query: Query<&mut Text, With<AmmoText>>,
+    players: Query<(&Player, &Ammo)>,
+    local: Res<LocalPlayers>
+) {
+    let mut players: Vec<_> = players.iter().filter(|(p, _)| local.0.contains(&p.id)).collect();
+    players.sort_by_key(|(p, _)| p.id);
+    let label = players.iter().map(|(p, ammo)| ammo_label(p.id, ammo)).collect::<Vec<_>>().join("\n");
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ComboText;
+
+pub fn spawn_combo_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font_size: 18.,
+            color: Color::srgb(1., 0.6, 0.2),
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            right: Val::Px(8.),
+            ..default()
+        }),
+        ComboText,
+    ));
+}
+
+pub fn update_combo_text(
+    mut query: Query<&mut Text, With<ComboText>>,
+    players: Query<(&Player, &Combo)>,
+    local: Res<LocalPlayers>,
+    frame: Res<FrameCounter>
+) {
+    let mut players: Vec<_> = players.iter().filter(|(p, _)| local.0.contains(&p.id)).collect();
+    players.sort_by_key(|(p, _)| p.id);
+    let label = players
+        .iter()
+        .map(|(p, combo)| format!("P{} x{} ({} pts)", p.id, combo.multiplier_at(frame.0), combo.points))
+        .collect::<Vec<_>>()
+        .join("\n");
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct SumoText;
+
+#[derive(Component, Default)]
+pub struct SafeZoneText;
+
+// big centered line announcing the winner, `T` tells which mode writes to it
+pub fn spawn_winner_text<T: Component + Default>(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(40.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 48., color: Color::WHITE, ..default() }),
+                T::default(),
+            ));
+        });
+}
+
+pub fn update_sumo_text(mut query: Query<&mut Text, With<SumoText>>, players: Query<(&Player, &KnockedOut)>) {
+    let mut players: Vec<_> = players.iter().map(|(p, k)| (p.id, *k)).collect();
+    players.sort_by_key(|(id, _)| *id);
+    let label = sumo_winner(&players).map_or(String::new(), |id| format!("P{id} wins"));
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+pub fn update_safe_zone_text(mut query: Query<&mut Text, With<SafeZoneText>>, players: Query<(&Player, &Health)>) {
+    let mut players: Vec<_> = players.iter().map(|(p, h)| (p.id, h.hp)).collect();
+    players.sort_by_key(|(id, _)| *id);
+    let label = zone_survivor(&players).map_or(String::new(), |id| format!("P{id} survives"));
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct EnergyText;
+
+pub fn spawn_energy_text(mut commands: Commands) {
+    let sections = (0..crate::teams::TEAM_COUNT).map(|team| {
+        TextSection::new("", TextStyle { font_size: 18., color: Team(team).color(), ..default() })
+    });
+    commands.spawn((
+        TextBundle::from_sections(sections).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        EnergyText,
+    ));
+}
+
+pub fn energy_label(team: usize, energy: u32) -> String {
+    let bar = "|".repeat((energy * 20 / TEAM_ENERGY_MAX) as usize);
+    format!("team {team} energy {bar:<20} {energy}\n")
+}
+
+pub fn update_energy_text(mut query: Query<&mut Text, With<EnergyText>>, energy: Res<TeamEnergy>) {
+    for mut text in query.iter_mut() {
+        for (team, section) in text.sections.iter_mut().enumerate() {
+            let label = energy_label(team, energy.pools[team]);
+            if section.value != label {
+                section.value = label;
+            }
+        }
+    }
+}
+
+const SCOREBOARD_KEY: KeyCode = KeyCode::Tab;
+
+#[derive(Component)]
+pub struct Scoreboard;
+
+// `players` is (handle, points) in any order, best first and equal points by handle so
+// rows don't swap places from one frame to the next
+pub fn scoreboard_order(players: &[(usize, u32)]) -> Vec<(usize, u32)> {
+    let mut rows = players.to_vec();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    rows
+}
+
+pub fn spawn_scoreboard(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(20.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 24., color: Color::WHITE, ..default() })
+                    .with_background_color(Color::srgba(0., 0., 0., 0.6)),
+                Scoreboard,
+            ));
+        });
+}
+
+// shown while the key is held, the rows are only rebuilt then
+pub fn update_scoreboard(
+    mut query: Query<(&mut Text, &Parent), With<Scoreboard>>,
+    mut nodes: Query<&mut Visibility>,
+    keys: Res<ButtonInput<KeyCode>>,
+    players: Query<(&Player, &Team, &Combo)>,
+    score: Res<Score>
+) {
+    let shown = keys.pressed(SCOREBOARD_KEY);
+    for (mut text, parent) in query.iter_mut() {
+        if let Ok(mut visibility) = nodes.get_mut(parent.get()) {
+            let wanted = if shown { Visibility::Inherited } else { Visibility::Hidden };
+            if *visibility != wanted {
+                *visibility = wanted;
+            }
+        }
+        if !shown {
+            continue;
+        }
+        let teams: Vec<_> = players.iter().map(|(p, team, _)| (p.id, team.0)).collect();
+        let points: Vec<_> = players.iter().map(|(p, _, combo)| (p.id, combo.points)).collect();
+        let mut label = score
+            .teams
+            .iter()
+            .enumerate()
+            .map(|(team, points)| format!("team {team}: {}", points / UPS as u32))
+            .collect::<Vec<_>>()
+            .join("   ");
+        for (id, points) in scoreboard_order(&points) {
+            let team = teams.iter().find(|(p, _)| *p == id).map_or(0, |(_, t)| *t);
+            label += &format!("\nP{id}  team {team}  {points} pts");
+        }
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_frames_convert_to_milliseconds() {
+        assert_eq!(delay_ms(0, 60.), 0.);
+        assert_eq!(delay_ms(3, 60.), 50.);
+        assert_eq!(delay_ms(2, 30.), 2000. / 30.);
+    }
+
+    #[test]
+    fn configured_colors_go_to_their_players_and_the_rest_use_the_palette() {
+        let colors: Vec<_> = ["#ff0000", "#0f0"].into_iter().map(parse_hex_color).collect::<Result<_, _>>().unwrap();
+        assert_eq!(player_color(&colors, Palette::Default, 0, 3), Color::srgb(1., 0., 0.));
+        assert_eq!(player_color(&colors, Palette::Default, 1, 3), Color::srgb(0., 1., 0.));
+        assert_eq!(player_color(&colors, Palette::Default, 2, 3), palette_color(Palette::Default, 2, 3));
+        assert_eq!(player_color(&colors, Palette::CbSafe, 2, 3), Color::srgb_u8(0x00, 0x9E, 0x73));
+
+        assert!(parse_hex_color("#12345").unwrap_err().contains("should be"));
+        assert!(parse_hex_color("#gg0000").unwrap_err().starts_with("`#gg0000`"));
+    }
+
+    #[test]
+    fn catch_up_text_shows_only_past_the_lag_the_spectator_tolerates() {
+        assert_eq!(catch_up_label(0), None);
+        assert_eq!(catch_up_label(crate::net::SPECTATOR_MAX_FRAMES_BEHIND), None);
+        let behind = crate::net::SPECTATOR_MAX_FRAMES_BEHIND + 1;
+        assert_eq!(catch_up_label(behind), Some(format!("catching up ({behind} frames behind)")));
+    }
+
+    #[test]
+    fn cb_safe_palette_is_distinct_then_cycles() {
+        let size = CB_SAFE_PALETTE.len();
+        let colors: Vec<_> = (0..size).map(|id| palette_color(Palette::CbSafe, id, size).to_srgba()).collect();
+        for (i, a) in colors.iter().enumerate() {
+            assert!(colors[i + 1..].iter().all(|b| a != b), "color {i} repeats");
+        }
+        for id in 0..3 * size {
+            assert_eq!(palette_color(Palette::CbSafe, id, 20), palette_color(Palette::CbSafe, id % size, 20));
+        }
+    }
+
+    #[test]
+    fn scoreboard_puts_the_best_first_and_ties_by_handle() {
+        let expected = vec![(2, 9), (0, 4), (3, 4), (4, 4), (1, 0)];
+        assert_eq!(scoreboard_order(&[(3, 4), (1, 0), (4, 4), (2, 9), (0, 4)]), expected);
+        assert_eq!(scoreboard_order(&[(4, 4), (0, 4), (1, 0), (3, 4), (2, 9)]), expected);
+        assert_eq!(scoreboard_order(&[]), vec![]);
+    }
+}